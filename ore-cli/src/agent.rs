@@ -0,0 +1,462 @@
+use std::{
+    fs::DirBuilder,
+    io::{BufRead, BufReader, Write},
+    os::unix::{
+        fs::{DirBuilderExt, PermissionsExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+};
+
+use ore::instruction::OreInstruction;
+use serde_json::{json, Value};
+use solana_program::{pubkey::Pubkey, system_instruction::SystemInstruction, system_program};
+use solana_sdk::{
+    compute_budget,
+    message::Message,
+    sanitize::Sanitize,
+    signature::{Signature, Signer, SignerError},
+};
+
+use crate::{
+    error::OreCliError,
+    signer::{SharedSigner, SignerSource},
+    Miner,
+};
+
+// Instruction types a key can be allowed to sign. Compute budget instructions are always allowed.
+const INSTRUCTION_TYPES: [&str; 9] = [
+    "reset",
+    "register",
+    "mine",
+    "claim",
+    "initialize",
+    "update_admin",
+    "update_difficulty",
+    "transfer",
+    "create_token_account",
+];
+
+// Highest compute unit price, in microlamports, a key signs for as fee payer unless its entry
+// sets `max_cu_price`. At the 1.4M compute unit cap this is at most 0.0014 SOL per transaction.
+const DEFAULT_MAX_CU_PRICE: u64 = 1_000_000;
+
+// The agent protocol is one JSON request per line, answered by one JSON response line holding
+// either a `result` or an `error`:
+//
+//   {"method": "get_pubkeys"}
+//     -> {"result": ["<PUBKEY>", ...]}
+//   {"method": "sign_message", "pubkey": "<PUBKEY>", "message": "<BASE58 MESSAGE>"}
+//     -> {"result": "<BASE58 SIGNATURE>"}
+//
+// The agent only signs legacy transaction messages that the key is a required signer of, and
+// whose instructions that include the key are all of a type the key is allowed to sign. A key
+// that pays the fees also refuses compute unit prices above its maximum.
+
+/// A signer whose key is held by an `ore agent` listening on a Unix socket.
+pub struct AgentSigner {
+    socket: String,
+    pubkey: Pubkey,
+}
+
+impl AgentSigner {
+    /// Connects to the agent at `socket` and selects `pubkey`, which may be omitted if the agent
+    /// holds a single key.
+    pub fn connect(socket: &str, pubkey: Option<Pubkey>) -> Result<Self, OreCliError> {
+        let agent_error = |err: String| OreCliError::Keypair(format!("agent {}: {}", socket, err));
+        let pubkeys: Vec<Pubkey> = request(socket, &json!({ "method": "get_pubkeys" }))
+            .map_err(agent_error)?
+            .as_array()
+            .map(|pubkeys| {
+                pubkeys
+                    .iter()
+                    .filter_map(|pubkey| Pubkey::from_str(pubkey.as_str()?).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let pubkey = match pubkey {
+            Some(pubkey) if pubkeys.contains(&pubkey) => pubkey,
+            Some(pubkey) => return Err(agent_error(format!("does not hold {}", pubkey))),
+            None if pubkeys.len() == 1 => pubkeys[0],
+            None => {
+                return Err(agent_error(format!(
+                    "holds {} keys, select one with ?pubkey=",
+                    pubkeys.len()
+                )))
+            }
+        };
+        Ok(Self {
+            socket: socket.to_string(),
+            pubkey,
+        })
+    }
+}
+
+impl Signer for AgentSigner {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        Ok(self.pubkey)
+    }
+
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        let result = request(
+            &self.socket,
+            &json!({
+                "method": "sign_message",
+                "pubkey": self.pubkey.to_string(),
+                "message": bs58::encode(message).into_string(),
+            }),
+        )
+        .map_err(SignerError::Connection)?;
+        result
+            .as_str()
+            .and_then(|signature| Signature::from_str(signature).ok())
+            .ok_or_else(|| SignerError::Protocol("invalid signature from agent".into()))
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+/// A key held by the agent, the instruction types it may sign, and the highest compute unit
+/// price it pays as fee payer.
+struct AgentKey {
+    signer: SharedSigner,
+    allow: Vec<String>,
+    max_cu_price: u64,
+}
+
+impl Miner {
+    /// Serves signing requests for the keys listed in the YAML file at `keys` on a Unix socket
+    /// at `socket`, until the process is stopped.
+    pub fn agent(&self, socket: String, keys: String) -> Result<(), OreCliError> {
+        let keys = Arc::new(read_agent_keys(&keys)?);
+        // Replace a socket left behind by an agent that is no longer running
+        if UnixStream::connect(&socket).is_ok() {
+            return Err(OreCliError::InvalidInput(format!("An agent is already listening on {}", socket)));
+        }
+        std::fs::remove_file(&socket).ok();
+        let listener = bind_private(&socket)
+            .map_err(|err| OreCliError::InvalidInput(format!("Failed to listen on {}: {}", socket, err)))?;
+        for key in keys.iter() {
+            println!(
+                "Serving {} (allowed: {}, max compute unit price: {})",
+                key.signer.pubkey(),
+                key.allow.join(", "),
+                key.max_cu_price
+            );
+        }
+        println!("Listening on {}", socket);
+        serve(listener, keys);
+        Ok(())
+    }
+}
+
+/// Binds a Unix socket at `socket` that only the current user can connect to. The socket is
+/// bound inside a fresh 0700 directory and restricted before it is moved into place, so it is
+/// never reachable with looser permissions.
+fn bind_private(socket: &str) -> std::io::Result<UnixListener> {
+    let socket = Path::new(socket);
+    let dir = socket
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(format!(".ore-agent-{}", std::process::id()));
+    DirBuilder::new().mode(0o700).create(&dir)?;
+    let staged = dir.join("agent.sock");
+    let listener = UnixListener::bind(&staged).and_then(|listener| {
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&staged, socket)?;
+        Ok(listener)
+    });
+    std::fs::remove_file(&staged).ok();
+    std::fs::remove_dir(&dir).ok();
+    listener
+}
+
+/// Answers the requests of every connection to `listener`, one thread per connection.
+fn serve(listener: UnixListener, keys: Arc<Vec<AgentKey>>) {
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let keys = keys.clone();
+        std::thread::spawn(move || {
+            let Ok(reader) = stream.try_clone() else {
+                return;
+            };
+            let mut stream = stream;
+            for line in BufReader::new(reader).lines() {
+                let Ok(line) = line else {
+                    return;
+                };
+                let response = match serde_json::from_str(&line) {
+                    Ok(request) => match handle(&keys, &request) {
+                        Ok(result) => json!({ "result": result }),
+                        Err(err) => json!({ "error": err }),
+                    },
+                    Err(err) => json!({ "error": format!("invalid request: {}", err) }),
+                };
+                if writeln!(stream, "{}", response).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+}
+
+/// Returns the result of a single agent request.
+fn handle(keys: &[AgentKey], request: &Value) -> Result<Value, String> {
+    match request["method"].as_str() {
+        Some("get_pubkeys") => Ok(json!(keys
+            .iter()
+            .map(|key| key.signer.pubkey().to_string())
+            .collect::<Vec<_>>())),
+        Some("sign_message") => {
+            let pubkey = request["pubkey"]
+                .as_str()
+                .and_then(|pubkey| Pubkey::from_str(pubkey).ok())
+                .ok_or("invalid pubkey")?;
+            let key = keys
+                .iter()
+                .find(|key| key.signer.pubkey() == pubkey)
+                .ok_or_else(|| format!("unknown key {}", pubkey))?;
+            let message = request["message"]
+                .as_str()
+                .and_then(|message| bs58::decode(message).into_vec().ok())
+                .ok_or("invalid message")?;
+            if let Err(err) = check_message(key, &message) {
+                println!("Refused to sign for {}: {}", pubkey, err);
+                return Err(err);
+            }
+            let signature = key
+                .signer
+                .try_sign_message(&message)
+                .map_err(|err| err.to_string())?;
+            Ok(json!(signature.to_string()))
+        }
+        _ => Err("unknown method".into()),
+    }
+}
+
+/// Checks that `message` is a transaction message `key` is allowed to sign. Only instructions
+/// that include the key among their accounts are checked, since the key's signature grants
+/// nothing to the others beyond paying fees.
+fn check_message(key: &AgentKey, message: &[u8]) -> Result<(), String> {
+    let message: Message = bincode::deserialize(message).map_err(|_| "not a transaction message")?;
+    message.sanitize().map_err(|_| "malformed transaction message")?;
+    let pubkey = key.signer.pubkey();
+    let key_index = message
+        .account_keys
+        .get(..message.header.num_required_signatures as usize)
+        .and_then(|signers| signers.iter().position(|signer| *signer == pubkey))
+        .ok_or("key is not a signer of the transaction")?;
+    if key_index == 0 {
+        check_cu_price(key, &message)?;
+    }
+    for ix in &message.instructions {
+        if !ix.accounts.contains(&(key_index as u8)) {
+            continue;
+        }
+        let program_id = message
+            .account_keys
+            .get(ix.program_id_index as usize)
+            .ok_or("invalid program id index")?;
+        if *program_id == compute_budget::id() {
+            continue;
+        }
+        match instruction_type(program_id, &ix.data) {
+            Some(kind) if key.allow.iter().any(|allowed| allowed == kind) => {}
+            Some(kind) => return Err(format!("{} instructions are not allowed", kind)),
+            None => return Err(format!("unknown instruction of program {}", program_id)),
+        }
+    }
+    Ok(())
+}
+
+/// Checks that `message`, paid for by `key`, doesn't price compute units above the key's maximum.
+fn check_cu_price(key: &AgentKey, message: &Message) -> Result<(), String> {
+    for ix in &message.instructions {
+        if message.account_keys.get(ix.program_id_index as usize) != Some(&compute_budget::id()) {
+            continue;
+        }
+        // SetComputeUnitPrice is variant 3 of ComputeBudgetInstruction, followed by a u64 price
+        if ix.data.first() != Some(&3) {
+            continue;
+        }
+        let price = ix.data[1..]
+            .try_into()
+            .map(u64::from_le_bytes)
+            .map_err(|_| "invalid compute unit price instruction")?;
+        if price > key.max_cu_price {
+            return Err(format!(
+                "compute unit price of {} microlamports exceeds the maximum of {}",
+                price, key.max_cu_price
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the type of an instruction of `program_id` with `data`, as named in an allowlist.
+fn instruction_type(program_id: &Pubkey, data: &[u8]) -> Option<&'static str> {
+    if *program_id == ore::ID {
+        return match OreInstruction::try_from(*data.first()?).ok()? {
+            OreInstruction::Reset => Some("reset"),
+            OreInstruction::Register => Some("register"),
+            OreInstruction::Mine => Some("mine"),
+            OreInstruction::Claim => Some("claim"),
+            OreInstruction::Initialize => Some("initialize"),
+            OreInstruction::UpdateAdmin => Some("update_admin"),
+            OreInstruction::UpdateDifficulty => Some("update_difficulty"),
+        };
+    }
+    if *program_id == system_program::id() {
+        return match bincode::deserialize(data).ok()? {
+            SystemInstruction::Transfer { .. } => Some("transfer"),
+            _ => None,
+        };
+    }
+    if *program_id == spl_associated_token_account::id() {
+        return Some("create_token_account");
+    }
+    None
+}
+
+/// Reads the keys an agent holds from a YAML file listing each key's signer, allowed
+/// instruction types and, optionally, the highest compute unit price it pays as fee payer:
+///
+/// ```yaml
+/// keys:
+///   - signer: /home/miner/keys/1.json
+///     allow: [mine, claim]
+///     max_cu_price: 100000
+/// ```
+fn read_agent_keys(path: &str) -> Result<Vec<AgentKey>, OreCliError> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|err| OreCliError::InvalidInput(format!("Failed to read {}: {}", path, err)))?;
+    let config: Value = serde_yaml::from_str(&contents)
+        .map_err(|err| OreCliError::InvalidInput(format!("Failed to parse {}: {}", path, err)))?;
+    let Some(entries) = config["keys"].as_array().filter(|entries| !entries.is_empty()) else {
+        return Err(OreCliError::InvalidInput(format!("No keys listed in {}", path)));
+    };
+    entries
+        .iter()
+        .map(|entry| {
+            let signer = entry["signer"]
+                .as_str()
+                .ok_or_else(|| OreCliError::InvalidInput(format!("Key without a signer in {}", path)))?;
+            let allow: Vec<String> = entry["allow"]
+                .as_array()
+                .map(|allow| allow.iter().filter_map(|kind| kind.as_str().map(str::to_string)).collect())
+                .unwrap_or_default();
+            if let Some(kind) = allow.iter().find(|kind| !INSTRUCTION_TYPES.contains(&kind.as_str())) {
+                return Err(OreCliError::InvalidInput(format!(
+                    "Unknown instruction type `{}` in {}, expected one of: {}",
+                    kind,
+                    path,
+                    INSTRUCTION_TYPES.join(", ")
+                )));
+            }
+            let max_cu_price = match &entry["max_cu_price"] {
+                Value::Null => DEFAULT_MAX_CU_PRICE,
+                value => value.as_u64().ok_or_else(|| {
+                    OreCliError::InvalidInput(format!("Invalid max_cu_price `{}` in {}", value, path))
+                })?,
+            };
+            Ok(AgentKey {
+                signer: SignerSource::from_str(signer)?.load()?,
+                allow,
+                max_cu_price,
+            })
+        })
+        .collect()
+}
+
+/// Sends `request` to the agent at `socket` and returns the result of its response.
+fn request(socket: &str, request: &Value) -> Result<Value, String> {
+    let mut stream = UnixStream::connect(socket).map_err(|err| err.to_string())?;
+    writeln!(stream, "{}", request).map_err(|err| err.to_string())?;
+    let mut line = String::new();
+    BufReader::new(stream)
+        .read_line(&mut line)
+        .map_err(|err| err.to_string())?;
+    let mut response: Value = serde_json::from_str(&line).map_err(|err| err.to_string())?;
+    if let Some(err) = response.get("error") {
+        return Err(err.as_str().unwrap_or("unknown error").to_string());
+    }
+    Ok(response["result"].take())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{os::unix::fs::PermissionsExt, sync::Arc};
+
+    use solana_program::{pubkey::Pubkey, system_instruction};
+    use solana_sdk::{
+        compute_budget::ComputeBudgetInstruction,
+        hash::Hash,
+        keccak::Hash as KeccakHash,
+        message::Message,
+        signature::{Keypair, Signer},
+        transaction::Transaction,
+    };
+
+    use super::{bind_private, serve, AgentKey, AgentSigner};
+
+    #[test]
+    fn signs_only_allowed_instructions() {
+        let socket = std::env::temp_dir().join(format!("ore-agent-test-{}.sock", std::process::id()));
+        let socket = socket.to_string_lossy().into_owned();
+        std::fs::remove_file(&socket).ok();
+        let listener = bind_private(&socket).unwrap();
+        let mode = std::fs::metadata(&socket).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let keypair = Keypair::new();
+        let pubkey = keypair.pubkey();
+        let keys = vec![AgentKey {
+            signer: Arc::new(keypair),
+            allow: vec!["mine".into()],
+            max_cu_price: 1_000,
+        }];
+        std::thread::spawn(move || serve(listener, Arc::new(keys)));
+
+        let signer = AgentSigner::connect(&socket, None).unwrap();
+        assert_eq!(signer.pubkey(), pubkey);
+        let mine_ix = ore::instruction::mine(pubkey, ore::BUS_ADDRESSES[0], KeccakHash::default().into(), 0);
+        let cu_limit_ix = ComputeBudgetInstruction::set_compute_unit_limit(3200);
+        let mut tx = Transaction::new_with_payer(&[cu_limit_ix, mine_ix], Some(&pubkey));
+        assert!(tx.try_sign(&[&signer], Hash::default()).is_ok());
+        assert!(tx.verify().is_ok());
+
+        let transfer_ix = system_instruction::transfer(&pubkey, &Pubkey::new_unique(), 1);
+        let mut tx = Transaction::new_with_payer(&[transfer_ix], Some(&pubkey));
+        assert!(tx.try_sign(&[&signer], Hash::default()).is_err());
+        assert!(signer.try_sign_message(b"not a transaction").is_err());
+
+        // Instructions that don't include the key, like a fee payer's transfer, aren't checked
+        let fee_payer = Keypair::new();
+        let transfer_ix = system_instruction::transfer(&fee_payer.pubkey(), &Pubkey::new_unique(), 1);
+        let mine_ix = ore::instruction::mine(pubkey, ore::BUS_ADDRESSES[0], KeccakHash::default().into(), 0);
+        let mut tx = Transaction::new_with_payer(&[transfer_ix, mine_ix], Some(&fee_payer.pubkey()));
+        assert!(tx.try_sign(&[&fee_payer as &dyn Signer, &signer], Hash::default()).is_ok());
+
+        // The key refuses to pay more than its maximum compute unit price
+        let mine_ix = ore::instruction::mine(pubkey, ore::BUS_ADDRESSES[0], KeccakHash::default().into(), 0);
+        let price_ix = ComputeBudgetInstruction::set_compute_unit_price(1_000);
+        let mut tx = Transaction::new_with_payer(&[price_ix, mine_ix.clone()], Some(&pubkey));
+        assert!(tx.try_sign(&[&signer], Hash::default()).is_ok());
+        let price_ix = ComputeBudgetInstruction::set_compute_unit_price(u64::MAX);
+        let mut tx = Transaction::new_with_payer(&[price_ix, mine_ix], Some(&pubkey));
+        assert!(tx.try_sign(&[&signer], Hash::default()).is_err());
+
+        // A message claiming more signers than it has keys is refused rather than crashing
+        let mut message = Message::new(&[], Some(&pubkey));
+        message.header.num_required_signatures = 5;
+        assert!(signer.try_sign_message(&bincode::serialize(&message).unwrap()).is_err());
+        assert!(signer.try_pubkey().is_ok());
+        assert!(AgentSigner::connect(&socket, None).is_ok());
+        std::fs::remove_file(&socket).ok();
+    }
+}
@@ -1,41 +1,39 @@
-use std::str::FromStr;
+use serde_json::json;
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::amount_to_ui_amount;
 
-use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_program::pubkey::Pubkey;
-use solana_sdk::{commitment_config::CommitmentConfig, signature::Signer};
-
-use crate::Miner;
+use crate::{
+    error::OreCliError,
+    output::ore_amount,
+    utils::{get_mining_state, parse_address},
+    Miner,
+};
 
 impl Miner {
-    pub async fn balance(&self, address: Option<String>) {
-        let signer = self.signer();
-        let address = if let Some(address) = address {
-            if let Ok(address) = Pubkey::from_str(&address) {
-                address
-            } else {
-                println!("Invalid address: {:?}", address);
-                return;
-            }
-        } else {
-            signer.pubkey()
+    pub async fn balance(&self, address: Option<String>) -> Result<(), OreCliError> {
+        let address = match address {
+            Some(address) => parse_address(&address)?,
+            None => self.signer()?.pubkey(),
         };
-        let client =
-            RpcClient::new_with_commitment(self.cluster.clone(), CommitmentConfig::confirmed());
-        let token_account_address = spl_associated_token_account::get_associated_token_address(
-            &address,
-            &ore::MINT_ADDRESS,
-        );
-        match client.get_token_account(&token_account_address).await {
-            Ok(token_account) => {
-                if let Some(token_account) = token_account {
-                    println!("{:} ORE", token_account.token_amount.ui_amount_string);
-                } else {
-                    println!("Account not found");
-                }
-            }
-            Err(err) => {
-                println!("{:?}", err);
-            }
+        let state = self
+            .rpc
+            .read(|client| async move { get_mining_state(&client, Some(address)).await })
+            .await?;
+        let json = json!({
+            "slot": state.slot,
+            "owner": address.to_string(),
+            "token_account": get_associated_token_address(&address, &ore::MINT_ADDRESS).to_string(),
+            "token_account_exists": state.ata.is_some(),
+            "balance": ore_amount(state.ata.map_or(0, |ata| ata.amount)),
+        });
+        if self.output.print_json(&json) {
+            return Ok(());
+        }
+        if let Some(ata) = state.ata {
+            println!("{:} ORE", amount_to_ui_amount(ata.amount, ore::TOKEN_DECIMALS));
+        } else {
+            println!("Account not found");
         }
+        Ok(())
     }
 }
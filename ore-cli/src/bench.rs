@@ -0,0 +1,54 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use solana_sdk::{
+    keccak::{hashv, Hash as KeccakHash},
+    pubkey::Pubkey,
+};
+
+use crate::{mine::find_next_hash_par, telemetry::expected_hashes, Miner};
+
+impl Miner {
+    pub fn bench(&self, max_threads: u64, duration: u64, nonces: Option<u64>) {
+        // Synthetic challenge. A zero difficulty is never satisfied, so every run lasts until stopped.
+        let challenge = hashv(&[b"ore-bench"]);
+        let authority = Pubkey::new_unique();
+        let difficulty = KeccakHash::new_from_array([0; 32]);
+        let duration = Duration::from_secs(duration);
+
+        let mut best: Option<(u64, f64)> = None;
+        for threads in 1..=max_threads {
+            let stop = Arc::new(AtomicBool::new(false));
+            let (_, stats) = find_next_hash_par(
+                challenge,
+                authority,
+                difficulty,
+                threads,
+                stop.clone(),
+                nonces,
+                |stats| {
+                    if nonces.is_none() && stats.elapsed >= duration {
+                        stop.store(true, Ordering::Relaxed);
+                    }
+                },
+            );
+            println!("Threads {}: {}", threads, stats);
+            if best.map_or(true, |(_, hashrate)| stats.hashrate > hashrate) {
+                best = Some((threads, stats.hashrate));
+            }
+        }
+
+        if let Some((threads, hashrate)) = best {
+            let expected_time = expected_hashes(ore::INITIAL_DIFFICULTY) / hashrate;
+            println!(
+                "Best: --threads {} ({:.0} H/s, {:.1}s expected per hash at initial difficulty)",
+                threads, hashrate, expected_time
+            );
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use solana_client::client_error::{ClientError, ClientErrorKind, Result as ClientResult};
+use solana_program::instruction::Instruction;
+use solana_sdk::{
+    signature::{Signature, Signer},
+    signer::SignerError,
+    transaction::{Transaction, TransactionError},
+};
+
+use crate::{
+    error::OreCliError, send_and_confirm::priced_ixs, signer::transaction_signers, Miner,
+};
+
+const BUNDLE_STATUS_RETRIES: usize = 15;
+const BUNDLE_STATUS_INTERVAL: Duration = Duration::from_secs(2);
+
+/// JSON-RPC client for a block-engine style bundle endpoint.
+pub struct BundleClient {
+    url: String,
+    http: reqwest::Client,
+}
+
+impl BundleClient {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .expect("Failed to build http client"),
+        }
+    }
+
+    /// Submits `txs` as one atomic bundle and returns the bundle id.
+    pub async fn send_bundle(&self, txs: &[Transaction]) -> ClientResult<String> {
+        let encoded = txs
+            .iter()
+            .map(|tx| bincode::serialize(tx).map(|bytes| bs58::encode(bytes).into_string()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| custom_error(format!("Failed to serialize transaction: {}", err)))?;
+        let result = self.request("sendBundle", json!([encoded])).await?;
+        result
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| custom_error(format!("Unexpected sendBundle result: {}", result)))
+    }
+
+    /// Returns the confirmation status of a bundle (e.g. "confirmed"), or `None` if the
+    /// endpoint does not know about it yet. A failed bundle is reported as a transaction error
+    /// when the endpoint's error decodes as one.
+    pub async fn get_bundle_status(&self, bundle_id: &str) -> ClientResult<Option<String>> {
+        let result = self
+            .request("getBundleStatuses", json!([[bundle_id]]))
+            .await?;
+        let Some(status) = result["value"].get(0).filter(|status| !status.is_null()) else {
+            return Ok(None);
+        };
+        if let Some(err) = status["err"].as_object().filter(|err| !err.contains_key("Ok")) {
+            let tx_err = err.get("Err").cloned().unwrap_or_else(|| status["err"].clone());
+            return Err(match serde_json::from_value::<TransactionError>(tx_err) {
+                Ok(tx_err) => tx_err.into(),
+                Err(_) => custom_error(format!("Bundle failed: {:?}", err)),
+            });
+        }
+        Ok(status["confirmation_status"].as_str().map(str::to_string))
+    }
+
+    /// Submits `txs` as a bundle and polls until it is confirmed. Polling only stops early if a
+    /// transaction of the bundle failed, since the bundle may still land after a failed poll.
+    pub async fn send_and_confirm_bundle(&self, txs: &[Transaction]) -> ClientResult<String> {
+        let bundle_id = self.send_bundle(txs).await?;
+        println!("Bundle sent with id: {}", bundle_id);
+        for _ in 0..BUNDLE_STATUS_RETRIES {
+            tokio::time::sleep(BUNDLE_STATUS_INTERVAL).await;
+            match self.get_bundle_status(&bundle_id).await {
+                Ok(Some(status)) if status == "confirmed" || status == "finalized" => {
+                    println!("Bundle {}!", status);
+                    return Ok(bundle_id);
+                }
+                Ok(_) => continue,
+                Err(err @ ClientError {
+                    kind: ClientErrorKind::TransactionError(_),
+                    ..
+                }) => return Err(err),
+                Err(err) => println!("Error fetching status of bundle {}: {}", bundle_id, err),
+            }
+        }
+        Err(custom_error(format!(
+            "Bundle {} was not confirmed after repeated attempts",
+            bundle_id
+        )))
+    }
+
+    async fn request(&self, method: &str, params: Value) -> ClientResult<Value> {
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        });
+        let response: Value = self
+            .http
+            .post(&self.url)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if let Some(err) = response.get("error") {
+            return Err(custom_error(format!("{} failed: {}", method, err)));
+        }
+        Ok(response["result"].clone())
+    }
+}
+
+impl Miner {
+    /// Sends `ixs` and the configured tip as an atomic bundle if a bundle endpoint is configured,
+    /// falling back to regular RPC submission if it is unreachable.
+    pub async fn send_and_confirm_bundle(
+        &self,
+        signer: &dyn Signer,
+        ixs: &[Instruction],
+    ) -> Result<Signature, OreCliError> {
+        let Some(bundle_client) = &self.bundle else {
+            return self.send_and_confirm(signer, ixs, false, false).await;
+        };
+        let fee_payer = self.fee_payer()?;
+        let payer: &dyn Signer = match &fee_payer {
+            Some(fee_payer) => fee_payer.as_ref(),
+            None => signer,
+        };
+        let (hash, _) = self
+            .rpc
+            .read(|client| async move {
+                Ok(client
+                    .get_latest_blockhash_with_commitment(client.commitment())
+                    .await?)
+            })
+            .await?;
+        let priced_ixs = priced_ixs(ixs, self.get_priority_fee(ixs).await);
+        let signing_error = |err: SignerError| OreCliError::Keypair(err.to_string());
+        let mut tx = Transaction::new_with_payer(&priced_ixs, Some(&payer.pubkey()));
+        tx.try_sign(&transaction_signers(payer, signer), hash)
+            .map_err(signing_error)?;
+        let mut txs = vec![tx];
+        let tip_ix = self.tip.tip_ix(&payer.pubkey());
+        if let Some(tip_ix) = &tip_ix {
+            let mut tip_tx = Transaction::new_with_payer(&[tip_ix.clone()], Some(&payer.pubkey()));
+            tip_tx.try_sign(&[payer], hash).map_err(signing_error)?;
+            txs.push(tip_tx);
+        }
+        match bundle_client.send_and_confirm_bundle(&txs).await {
+            Ok(_) => {
+                if tip_ix.is_some() {
+                    self.tip.record_tip();
+                }
+                Ok(txs[0].signatures[0])
+            }
+            Err(ClientError {
+                kind: ClientErrorKind::Reqwest(err),
+                ..
+            }) if err.is_connect() || err.is_timeout() => {
+                println!("Bundle endpoint unreachable ({}), falling back to RPC", err);
+                self.send_and_confirm_tipped(signer, ixs, tip_ix, false, false)
+                    .await
+            }
+            Err(ClientError {
+                kind: ClientErrorKind::TransactionError(err),
+                ..
+            }) => Err(OreCliError::from_transaction_error(err, &priced_ixs)),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+fn custom_error(msg: String) -> ClientError {
+    ClientError {
+        request: None,
+        kind: ClientErrorKind::Custom(msg),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use solana_sdk::{
+        hash::Hash,
+        instruction::InstructionError,
+        signature::Keypair,
+        signer::Signer,
+        system_instruction,
+        transaction::{Transaction, TransactionError},
+    };
+    use tokio::net::TcpListener;
+
+    use super::BundleClient;
+    use crate::test_utils::mock_server;
+
+    fn test_transactions() -> Vec<Transaction> {
+        let payer = Keypair::new();
+        let ix = system_instruction::transfer(&payer.pubkey(), &payer.pubkey(), 1);
+        vec![Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::default(),
+        )]
+    }
+
+    #[tokio::test]
+    async fn send_bundle_and_poll_status() {
+        let url = mock_server(|method, params| match method {
+            "sendBundle" => {
+                assert_eq!(params[0].as_array().unwrap().len(), 1);
+                json!("bundle-1")
+            }
+            "getBundleStatuses" => {
+                assert_eq!(params[0][0], "bundle-1");
+                json!({
+                    "context": { "slot": 1 },
+                    "value": [{
+                        "bundle_id": "bundle-1",
+                        "confirmation_status": "confirmed",
+                        "err": { "Ok": null },
+                    }],
+                })
+            }
+            _ => panic!("unexpected method {}", method),
+        })
+        .await;
+        let client = BundleClient::new(url);
+        let bundle_id = client.send_bundle(&test_transactions()).await.unwrap();
+        assert_eq!(bundle_id, "bundle-1");
+        let status = client.get_bundle_status(&bundle_id).await.unwrap();
+        assert_eq!(status.as_deref(), Some("confirmed"));
+    }
+
+    #[tokio::test]
+    async fn failed_bundle_is_a_transaction_error() {
+        let url = mock_server(|_, _| {
+            json!({
+                "context": { "slot": 1 },
+                "value": [{
+                    "bundle_id": "bundle-1",
+                    "confirmation_status": "processed",
+                    "err": { "Err": { "InstructionError": [1, { "Custom": 2 }] } },
+                }],
+            })
+        })
+        .await;
+        let client = BundleClient::new(url);
+        let err = client.get_bundle_status("bundle-1").await.unwrap_err();
+        match err.kind {
+            solana_client::client_error::ClientErrorKind::TransactionError(err) => assert_eq!(
+                err,
+                TransactionError::InstructionError(1, InstructionError::Custom(2))
+            ),
+            kind => panic!("unexpected error {:?}", kind),
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_bundle_has_no_status() {
+        let url = mock_server(|_, _| json!({ "context": { "slot": 1 }, "value": [null] })).await;
+        let client = BundleClient::new(url);
+        assert_eq!(client.get_bundle_status("missing").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn unreachable_endpoint_is_a_transport_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let client = BundleClient::new(url);
+        let err = client.send_bundle(&test_transactions()).await.unwrap_err();
+        match err.kind {
+            solana_client::client_error::ClientErrorKind::Reqwest(err) => assert!(err.is_connect()),
+            kind => panic!("unexpected error {:?}", kind),
+        }
+    }
+}
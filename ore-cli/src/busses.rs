@@ -1,28 +1,120 @@
-use ore::{state::Bus, utils::AccountDeserialize, BUS_ADDRESSES};
-use solana_client::{client_error::Result, nonblocking::rpc_client::RpcClient};
-use solana_sdk::commitment_config::CommitmentConfig;
+use ore::{state::Bus, BUS_ADDRESSES, BUS_COUNT};
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+use serde_json::json;
 
-use crate::Miner;
+use crate::{error::OreCliError, output::ore_amount, utils::get_mining_state, Miner};
+
+/// Strategy for choosing which bus to submit a mine transaction to.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BusStrategy {
+    /// Any bus with enough rewards, chosen uniformly
+    Random,
+
+    /// The bus with the most rewards
+    Richest,
+
+    /// Any bus with enough rewards, weighted by its remaining rewards
+    #[default]
+    Weighted,
+}
 
 impl Miner {
-    pub async fn busses(&self) {
-        let client =
-            RpcClient::new_with_commitment(self.cluster.clone(), CommitmentConfig::confirmed());
-        for address in BUS_ADDRESSES.iter() {
-            let data = client.get_account_data(address).await.unwrap();
-            match Bus::try_from_bytes(&data) {
-                Ok(bus) => {
-                    println!("Bus {}: {:} ORE", bus.id, bus.rewards);
-                }
-                Err(_) => {}
+    pub async fn busses(&self) -> Result<(), OreCliError> {
+        let state = self
+            .rpc
+            .read(|client| async move { get_mining_state(&client, None).await })
+            .await?;
+        let json = json!({
+            "slot": state.slot,
+            "busses": state
+                .busses
+                .iter()
+                .map(|bus| {
+                    json!({
+                        "id": bus.id,
+                        "address": BUS_ADDRESSES[bus.id as usize].to_string(),
+                        "rewards": ore_amount(bus.rewards),
+                    })
+                })
+                .collect::<Vec<_>>(),
+        });
+        if self.output.print_json(&json) {
+            return Ok(());
+        }
+        for bus in state.busses {
+            println!("Bus {}: {:} ORE", bus.id, bus.rewards);
+        }
+        Ok(())
+    }
+}
+
+/// Picks a bus with at least `reward_rate` rewards remaining according to `strategy`.
+pub fn select_bus(
+    busses: &[Bus],
+    reward_rate: u64,
+    strategy: BusStrategy,
+    rng: &mut impl Rng,
+) -> Option<Bus> {
+    let eligible: Vec<&Bus> = busses
+        .iter()
+        .filter(|bus| bus.rewards >= reward_rate && (bus.id as usize) < BUS_COUNT)
+        .collect();
+    if eligible.is_empty() {
+        return None;
+    }
+    let bus = match strategy {
+        BusStrategy::Random => eligible[rng.gen_range(0..eligible.len())],
+        BusStrategy::Richest => *eligible.iter().max_by_key(|bus| bus.rewards)?,
+        BusStrategy::Weighted => {
+            let weights = eligible.iter().map(|bus| bus.rewards.max(1));
+            let dist = WeightedIndex::new(weights).ok()?;
+            eligible[dist.sample(rng)]
+        }
+    };
+    Some(*bus)
+}
+
+#[cfg(test)]
+mod tests {
+    use ore::state::Bus;
+
+    use super::{select_bus, BusStrategy};
+
+    fn busses(rewards: &[u64]) -> Vec<Bus> {
+        rewards
+            .iter()
+            .enumerate()
+            .map(|(id, rewards)| Bus {
+                id: id as u64,
+                rewards: *rewards,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn selects_only_busses_that_can_pay_the_reward_rate() {
+        let busses = busses(&[5, 100, 9, 50]);
+        let mut rng = rand::thread_rng();
+        for strategy in [BusStrategy::Random, BusStrategy::Weighted] {
+            for _ in 0..100 {
+                let bus = select_bus(&busses, 10, strategy, &mut rng).unwrap();
+                assert!(bus.id == 1 || bus.id == 3);
             }
         }
     }
 
-    pub async fn get_bus(&self, id: usize) -> Result<Bus> {
-        let client =
-            RpcClient::new_with_commitment(self.cluster.clone(), CommitmentConfig::confirmed());
-        let data = client.get_account_data(&BUS_ADDRESSES[id]).await?;
-        Ok(*Bus::try_from_bytes(&data).unwrap())
+    #[test]
+    fn richest_selects_the_bus_with_most_rewards() {
+        let busses = busses(&[20, 100, 30, 99]);
+        let bus = select_bus(&busses, 10, BusStrategy::Richest, &mut rand::thread_rng()).unwrap();
+        assert_eq!(bus.id, 1);
+    }
+
+    #[test]
+    fn selects_nothing_when_all_busses_are_drained() {
+        let busses = busses(&[0, 5, 9, 1]);
+        for strategy in [BusStrategy::Random, BusStrategy::Richest, BusStrategy::Weighted] {
+            assert!(select_bus(&busses, 10, strategy, &mut rand::thread_rng()).is_none());
+        }
     }
 }
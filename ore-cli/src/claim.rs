@@ -1,64 +1,46 @@
-use std::str::FromStr;
-
-use ore::{self, state::Proof, utils::AccountDeserialize};
-use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_program::pubkey::Pubkey;
-use solana_sdk::{
-    commitment_config::CommitmentConfig, compute_budget::ComputeBudgetInstruction,
-    signature::Signer,
-};
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
 
-use crate::{cu_limits::CU_LIMIT_CLAIM, utils::proof_pubkey, Miner};
+use crate::{
+    cu_limits::CU_LIMIT_CLAIM,
+    error::OreCliError,
+    utils::{get_mining_state, parse_address},
+    Miner,
+};
 
 impl Miner {
-    pub async fn claim(&self, cluster: String, beneficiary: Option<String>, amount: Option<f64>) {
-        let signer = self.signer();
+    pub async fn claim(&self, beneficiary: Option<String>, amount: Option<f64>) -> Result<(), OreCliError> {
+        let signer = self.signer()?;
         let pubkey = signer.pubkey();
-        let client = RpcClient::new_with_commitment(cluster, CommitmentConfig::confirmed());
         let beneficiary = match beneficiary {
-            Some(beneficiary) => {
-                Pubkey::from_str(&beneficiary).expect("Failed to parse beneficiary address")
-            }
-            None => self.initialize_ata().await,
+            Some(beneficiary) => parse_address(&beneficiary)?,
+            None => self.initialize_ata().await?,
         };
         let amount = if let Some(amount) = amount {
             (amount * 10f64.powf(ore::TOKEN_DECIMALS as f64)) as u64
         } else {
-            match client.get_account(&proof_pubkey(pubkey)).await {
-                Ok(proof_account) => {
-                    let proof = Proof::try_from_bytes(&proof_account.data).unwrap();
-                    proof.claimable_rewards
-                }
-                Err(err) => {
-                    println!("Error looking up claimable rewards: {:?}", err);
-                    return;
-                }
-            }
+            get_mining_state(&self.rpc_client(), Some(pubkey))
+                .await?
+                .proof
+                .ok_or_else(|| OreCliError::AccountNotFound(format!("proof of {}", pubkey)))?
+                .claimable_rewards
         };
         let amountf = (amount as f64) / (10f64.powf(ore::TOKEN_DECIMALS as f64));
         let cu_limit_ix = ComputeBudgetInstruction::set_compute_unit_limit(CU_LIMIT_CLAIM);
-        let cu_price_ix = ComputeBudgetInstruction::set_compute_unit_price(self.priority_fee);
         let ix = ore::instruction::claim(pubkey, beneficiary, amount);
         println!("Submitting claim transaction...");
-        match self
-            .send_and_confirm(&[cu_limit_ix, cu_price_ix, ix], false, false)
-            .await
-        {
-            Ok(sig) => {
-                println!("Claimed {:} ORE to account {:}", amountf, beneficiary);
-                println!("{:?}", sig);
-            }
-            Err(err) => {
-                println!("Error: {:?}", err);
-            }
-        }
+        let sig = self
+            .send_and_confirm(signer.as_ref(), &[cu_limit_ix, ix], false, false)
+            .await?;
+        println!("Claimed {:} ORE to account {:}", amountf, beneficiary);
+        println!("{:?}", sig);
+        Ok(())
     }
 
-    async fn initialize_ata(&self) -> Pubkey {
+    async fn initialize_ata(&self) -> Result<Pubkey, OreCliError> {
         // Initialize client.
-        let signer = self.signer();
-        let client =
-            RpcClient::new_with_commitment(self.cluster.clone(), CommitmentConfig::confirmed());
+        let signer = self.signer()?;
+        let client = self.rpc_client();
 
         // Build instructions.
         let token_account_pubkey = spl_associated_token_account::get_associated_token_address(
@@ -68,23 +50,22 @@ impl Miner {
 
         // Check if ata already exists
         if let Ok(Some(_ata)) = client.get_token_account(&token_account_pubkey).await {
-            return token_account_pubkey;
+            return Ok(token_account_pubkey);
         }
 
-        // Sign and send transaction.
+        // Sign and send transaction. The fee payer, if any, funds the account's rent.
+        let funder = self.fee_payer()?.map_or(signer.pubkey(), |payer| payer.pubkey());
         let ix = spl_associated_token_account::instruction::create_associated_token_account(
-            &signer.pubkey(),
+            &funder,
             &signer.pubkey(),
             &ore::MINT_ADDRESS,
             &spl_token::id(),
         );
         println!("Creating token account {}...", token_account_pubkey);
-        match self.send_and_confirm(&[ix], true, false).await {
-            Ok(_sig) => println!("Created token account {:?}", token_account_pubkey),
-            Err(e) => println!("Transaction failed: {:?}", e),
-        }
+        self.send_and_confirm(signer.as_ref(), &[ix], true, false).await?;
+        println!("Created token account {:?}", token_account_pubkey);
 
         // Return token account address
-        token_account_pubkey
+        Ok(token_account_pubkey)
     }
 }
@@ -0,0 +1,150 @@
+use std::fmt;
+
+use ore::error::OreError;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_program::instruction::{Instruction, InstructionError};
+use solana_sdk::transaction::TransactionError;
+
+// Every error the Ore program can return, for decoding custom error codes
+const ORE_ERRORS: [OreError; 7] = [
+    OreError::NotStarted,
+    OreError::NeedsReset,
+    OreError::ResetTooEarly,
+    OreError::HashInvalid,
+    OreError::DifficultyNotSatisfied,
+    OreError::BusRewardsInsufficient,
+    OreError::ClaimTooLarge,
+];
+
+/// Errors that end a command. Each kind maps to its own process exit code.
+#[derive(Debug)]
+pub enum OreCliError {
+    /// A command line argument could not be parsed.
+    InvalidInput(String),
+
+    /// An RPC request failed or a transaction could not be landed.
+    Rpc(ClientError),
+
+    /// A required account does not exist.
+    AccountNotFound(String),
+
+    /// An account exists but its data could not be parsed.
+    Deserialization(String),
+
+    /// The keypair is missing or could not be read.
+    Keypair(String),
+
+    /// The signer does not hold enough SOL to pay for the transaction.
+    InsufficientFunds,
+
+    /// A transaction landed but the program returned an error.
+    Program(TransactionError),
+
+    /// A transaction landed or was simulated but an Ore instruction returned an error.
+    Ore(OreError),
+}
+
+impl OreCliError {
+    /// Returns the process exit code for this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            OreCliError::InvalidInput(_) => 2,
+            OreCliError::Rpc(_) => 3,
+            OreCliError::AccountNotFound(_) => 4,
+            OreCliError::Deserialization(_) => 5,
+            OreCliError::Keypair(_) => 6,
+            OreCliError::InsufficientFunds => 7,
+            OreCliError::Program(_) | OreCliError::Ore(_) => 8,
+        }
+    }
+
+    /// Returns the error for a failed transaction made of `ixs`, decoding it if an Ore
+    /// instruction failed.
+    pub fn from_transaction_error(err: TransactionError, ixs: &[Instruction]) -> Self {
+        match decode_ore_error(&err, ixs) {
+            Some(err) => OreCliError::Ore(err),
+            None => OreCliError::Program(err),
+        }
+    }
+}
+
+impl fmt::Display for OreCliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OreCliError::InvalidInput(msg) => write!(f, "{}", msg),
+            OreCliError::Rpc(err) => write!(f, "RPC error: {}", err),
+            OreCliError::AccountNotFound(account) => write!(f, "Account not found: {}", account),
+            OreCliError::Deserialization(account) => write!(f, "Failed to parse {}", account),
+            OreCliError::Keypair(msg) => write!(f, "Keypair error: {}", msg),
+            OreCliError::InsufficientFunds => write!(f, "Insufficient SOL balance"),
+            OreCliError::Program(err) => write!(f, "Transaction failed: {}", err),
+            OreCliError::Ore(err) => write!(
+                f,
+                "Transaction failed: {:?} ({}). {}",
+                err,
+                err,
+                suggested_action(*err)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for OreCliError {}
+
+impl From<ClientError> for OreCliError {
+    fn from(err: ClientError) -> Self {
+        match err.kind {
+            ClientErrorKind::TransactionError(err) => OreCliError::Program(err),
+            _ => OreCliError::Rpc(err),
+        }
+    }
+}
+
+/// Decodes `err` into an Ore program error if it was returned by an Ore instruction in `ixs`.
+/// Custom error codes of other programs, such as the system program, are not decoded.
+pub fn decode_ore_error(err: &TransactionError, ixs: &[Instruction]) -> Option<OreError> {
+    let TransactionError::InstructionError(i, InstructionError::Custom(code)) = err else {
+        return None;
+    };
+    if ixs.get(*i as usize)?.program_id != ore::ID {
+        return None;
+    }
+    ORE_ERRORS.into_iter().find(|err| u32::from(*err) == *code)
+}
+
+/// Returns what the user or the miner should do about `err`.
+pub fn suggested_action(err: OreError) -> &'static str {
+    match err {
+        OreError::NotStarted => "Wait for mining to start.",
+        OreError::NeedsReset => "Reset the epoch, then resubmit.",
+        OreError::ResetTooEarly => "The epoch was already reset. Keep mining.",
+        OreError::HashInvalid => "The challenge changed. Fetch the proof again and mine a new hash.",
+        OreError::DifficultyNotSatisfied => {
+            "The difficulty changed. Fetch the treasury again and mine a new hash."
+        }
+        OreError::BusRewardsInsufficient => "Submit to another bus.",
+        OreError::ClaimTooLarge => "Claim at most the proof's claimable rewards.",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ore::error::OreError;
+    use solana_program::instruction::InstructionError;
+    use solana_sdk::{pubkey::Pubkey, system_instruction, transaction::TransactionError};
+
+    use super::decode_ore_error;
+
+    #[test]
+    fn decodes_only_errors_from_ore_instructions() {
+        let authority = Pubkey::new_unique();
+        let ixs = [
+            system_instruction::transfer(&authority, &Pubkey::new_unique(), 1),
+            ore::instruction::reset(authority),
+        ];
+        let err = |i| TransactionError::InstructionError(i, InstructionError::Custom(1));
+        assert_eq!(decode_ore_error(&err(1), &ixs), Some(OreError::NeedsReset));
+        assert_eq!(decode_ore_error(&err(0), &ixs), None);
+        assert_eq!(decode_ore_error(&err(2), &ixs), None);
+    }
+}
@@ -0,0 +1,390 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Nonce,
+};
+use serde_json::{json, Value};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+
+use crate::{error::OreCliError, signer::SignerSource, Miner};
+
+// Environment variable holding the keystore password. The password is prompted for if it is unset.
+const PASSWORD_VAR: &str = "ORE_KEYSTORE_PASSWORD";
+
+// Scrypt cost of new keys: 2^15 rounds over 8 blocks, using 32 MiB of memory
+const SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+// Highest scrypt cost accepted from a key file, using at most 2 GiB of memory and four passes
+// over it, so a crafted file can't exhaust the machine
+const MAX_SCRYPT_LOG_N: u8 = 20;
+const MAX_SCRYPT_R: u32 = 16;
+const MAX_SCRYPT_P: u32 = 4;
+
+// Version of the key file format
+const KEY_VERSION: u64 = 1;
+
+impl Miner {
+    /// Generates a new key and stores it in the keystore as `name`.
+    pub fn keys_new(&self, keystore: Option<String>, name: String) -> Result<(), OreCliError> {
+        let path = key_file(keystore.as_deref(), &name)?;
+        let keypair = Keypair::new();
+        write_key(&path, &keypair, &new_password()?)?;
+        println!("Created key {} ({})", name, keypair.pubkey());
+        Ok(())
+    }
+
+    /// Encrypts the key of `source` into the keystore as `name`.
+    pub fn keys_import(
+        &self,
+        keystore: Option<String>,
+        name: String,
+        source: SignerSource,
+    ) -> Result<(), OreCliError> {
+        let path = key_file(keystore.as_deref(), &name)?;
+        let keypair = source.load_keypair()?;
+        write_key(&path, &keypair, &new_password()?)?;
+        println!("Imported key {} ({})", name, keypair.pubkey());
+        Ok(())
+    }
+
+    /// Decrypts the key `name` into a keypair file at `outfile`, or prints its base58 secret key.
+    pub fn keys_export(
+        &self,
+        keystore: Option<String>,
+        name: String,
+        outfile: Option<String>,
+    ) -> Result<(), OreCliError> {
+        let keypair = read_key(&key_file(keystore.as_deref(), &name)?)?;
+        let Some(outfile) = outfile else {
+            println!("{}", keypair.to_base58_string());
+            return Ok(());
+        };
+        let contents = serde_json::to_string(&keypair.to_bytes().to_vec())
+            .map_err(|err| OreCliError::Keypair(err.to_string()))?;
+        create_private_file(Path::new(&outfile))
+            .and_then(|mut file| file.write_all(contents.as_bytes()))
+            .map_err(|err| OreCliError::Keypair(format!("{}: {}", outfile, err)))?;
+        println!("Exported key {} ({}) to {}", name, keypair.pubkey(), outfile);
+        Ok(())
+    }
+
+    /// Lists the name and pubkey of every key in the keystore.
+    pub fn keys_list(&self, keystore: Option<String>) -> Result<(), OreCliError> {
+        let dir = keystore_dir(keystore.as_deref())?;
+        let keys = list_keys(&dir);
+
+        let json = json!({
+            "keystore": dir.to_string_lossy(),
+            "keys": keys
+                .iter()
+                .map(|(name, pubkey)| json!({ "name": name, "pubkey": pubkey.to_string() }))
+                .collect::<Vec<_>>(),
+        });
+        if self.output.print_json(&json) {
+            return Ok(());
+        }
+        if keys.is_empty() {
+            println!("No keys in {}", dir.display());
+        }
+        for (name, pubkey) in &keys {
+            println!("{} {}", name, pubkey);
+        }
+        Ok(())
+    }
+}
+
+/// A key file of the keystore, with its secret key still encrypted.
+struct KeyFile {
+    pubkey: Pubkey,
+    contents: Value,
+}
+
+/// Returns the path of the key file a `keystore:` signer refers to: `name` itself if it is a path,
+/// and the key of that name in the default keystore otherwise.
+pub fn key_path(name: &str) -> Result<PathBuf, OreCliError> {
+    if name.contains(std::path::is_separator) {
+        return Ok(PathBuf::from(name));
+    }
+    key_file(None, name)
+}
+
+/// Decrypts the key file at `path`, with the password taken from the environment or a prompt.
+pub fn read_key(path: &Path) -> Result<Keypair, OreCliError> {
+    let key_file = read_key_file(path)?;
+    let password = match std::env::var(PASSWORD_VAR) {
+        Ok(password) => password,
+        Err(_) => rpassword::prompt_password(format!("Password for {}: ", path.display()))
+            .map_err(|err| OreCliError::Keypair(format!("failed to read password: {}", err)))?,
+    };
+    decrypt_key(&key_file.contents, &password)
+        .map_err(|err| OreCliError::Keypair(format!("{}: {}", path.display(), err)))
+}
+
+/// Returns the keystore directory, which defaults to `~/.config/ore/keystore`.
+fn keystore_dir(keystore: Option<&str>) -> Result<PathBuf, OreCliError> {
+    if let Some(keystore) = keystore {
+        return Ok(PathBuf::from(keystore));
+    }
+    dirs_next::home_dir()
+        .map(|home| home.join(".config").join("ore").join("keystore"))
+        .ok_or_else(|| OreCliError::Keypair("Could not find the home directory, pass --keystore".into()))
+}
+
+/// Returns the path of the key `name` in the keystore.
+fn key_file(keystore: Option<&str>, name: &str) -> Result<PathBuf, OreCliError> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+    if !valid {
+        return Err(OreCliError::InvalidInput(format!(
+            "Invalid key name `{}`, use letters, digits, `-`, `_` and `.`",
+            name
+        )));
+    }
+    Ok(keystore_dir(keystore)?.join(format!("{}.json", name)))
+}
+
+fn read_key_file(path: &Path) -> Result<KeyFile, OreCliError> {
+    let contents: Value = fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|contents| serde_json::from_str(&contents).map_err(|err| err.to_string()))
+        .map_err(|err| OreCliError::Keypair(format!("{}: {}", path.display(), err)))?;
+    let pubkey = contents["pubkey"]
+        .as_str()
+        .and_then(|pubkey| Pubkey::from_str(pubkey).ok())
+        .ok_or_else(|| OreCliError::Keypair(format!("{}: not a keystore key file", path.display())))?;
+    Ok(KeyFile { pubkey, contents })
+}
+
+/// Returns the name and pubkey of every key file in `dir`, sorted by name. Files that aren't
+/// readable key files are skipped with a warning.
+fn list_keys(dir: &Path) -> Vec<(String, Pubkey)> {
+    let mut keys = vec![];
+    if let Ok(entries) = fs::read_dir(dir) {
+        for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+            if path.extension().map_or(true, |ext| ext != "json") {
+                continue;
+            }
+            let name = path.file_stem().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+            match read_key_file(&path) {
+                Ok(key_file) => keys.push((name, key_file.pubkey)),
+                Err(err) => eprintln!("warning: skipping unreadable key file: {}", err),
+            }
+        }
+    }
+    keys.sort();
+    keys
+}
+
+/// Encrypts `keypair` and writes it to a new key file at `path`.
+fn write_key(path: &Path, keypair: &Keypair, password: &str) -> Result<(), OreCliError> {
+    let contents = encrypt_key(keypair, password, SCRYPT_LOG_N)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| OreCliError::Keypair(format!("{}: {}", dir.display(), err)))?;
+    }
+    create_private_file(path)
+        .and_then(|mut file| writeln!(file, "{}", contents))
+        .map_err(|err| match err.kind() {
+            std::io::ErrorKind::AlreadyExists => {
+                OreCliError::InvalidInput(format!("Key {} already exists", path.display()))
+            }
+            _ => OreCliError::Keypair(format!("{}: {}", path.display(), err)),
+        })
+}
+
+/// Creates a file only its owner can read, failing if it already exists.
+fn create_private_file(path: &Path) -> std::io::Result<fs::File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)
+}
+
+/// Returns the password for a new key, asking for it twice if it is prompted for.
+fn new_password() -> Result<String, OreCliError> {
+    let password = match std::env::var(PASSWORD_VAR) {
+        Ok(password) => password,
+        Err(_) => {
+            let prompt = |prompt: &str| {
+                rpassword::prompt_password(prompt)
+                    .map_err(|err| OreCliError::Keypair(format!("failed to read password: {}", err)))
+            };
+            let password = prompt("New keystore password: ")?;
+            if prompt("Confirm password: ")? != password {
+                return Err(OreCliError::InvalidInput("Passwords do not match".into()));
+            }
+            password
+        }
+    };
+    if password.is_empty() {
+        return Err(OreCliError::InvalidInput("Password must not be empty".into()));
+    }
+    Ok(password)
+}
+
+/// Encrypts the secret key of `keypair` with AES-256-GCM, under a key derived from `password`
+/// with scrypt. The pubkey is stored in the clear, and authenticated with the secret key.
+fn encrypt_key(keypair: &Keypair, password: &str, log_n: u8) -> Result<Value, OreCliError> {
+    let salt: [u8; 16] = rand::random();
+    let nonce: [u8; 12] = rand::random();
+    let cipher = derive_cipher(password, &salt, log_n, SCRYPT_R, SCRYPT_P)?;
+    let pubkey = keypair.pubkey();
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &keypair.to_bytes(),
+                aad: pubkey.as_ref(),
+            },
+        )
+        .map_err(|_| OreCliError::Keypair("failed to encrypt key".into()))?;
+    Ok(json!({
+        "version": KEY_VERSION,
+        "pubkey": pubkey.to_string(),
+        "kdf": {
+            "name": "scrypt",
+            "log_n": log_n,
+            "r": SCRYPT_R,
+            "p": SCRYPT_P,
+            "salt": bs58::encode(salt).into_string(),
+        },
+        "cipher": {
+            "name": "aes-256-gcm",
+            "nonce": bs58::encode(nonce).into_string(),
+            "ciphertext": bs58::encode(ciphertext).into_string(),
+        },
+    }))
+}
+
+/// Decrypts a key encrypted by `encrypt_key`.
+fn decrypt_key(contents: &Value, password: &str) -> Result<Keypair, String> {
+    if contents["version"].as_u64() != Some(KEY_VERSION) {
+        return Err("unsupported key file version".into());
+    }
+    let kdf = &contents["kdf"];
+    let cipher = &contents["cipher"];
+    if kdf["name"] != "scrypt" || cipher["name"] != "aes-256-gcm" {
+        return Err("unsupported encryption".into());
+    }
+    let bytes = |value: &Value| {
+        value
+            .as_str()
+            .and_then(|value| bs58::decode(value).into_vec().ok())
+            .ok_or_else(|| "malformed key file".to_string())
+    };
+    let param = |name: &str| kdf[name].as_u64().ok_or_else(|| "malformed key file".to_string());
+    let pubkey = contents["pubkey"]
+        .as_str()
+        .and_then(|pubkey| Pubkey::from_str(pubkey).ok())
+        .ok_or("malformed key file")?;
+    let nonce = bytes(&cipher["nonce"])?;
+    if nonce.len() != 12 {
+        return Err("malformed key file".into());
+    }
+    let cipher_key = derive_cipher(
+        password,
+        &bytes(&kdf["salt"])?,
+        u8::try_from(param("log_n")?)
+            .ok()
+            .filter(|log_n| *log_n <= MAX_SCRYPT_LOG_N)
+            .ok_or("unsupported scrypt cost")?,
+        u32::try_from(param("r")?)
+            .ok()
+            .filter(|r| *r <= MAX_SCRYPT_R)
+            .ok_or("unsupported scrypt cost")?,
+        u32::try_from(param("p")?)
+            .ok()
+            .filter(|p| *p <= MAX_SCRYPT_P)
+            .ok_or("unsupported scrypt cost")?,
+    )
+    .map_err(|err| err.to_string())?;
+    let secret = cipher_key
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &bytes(&cipher["ciphertext"])?,
+                aad: pubkey.as_ref(),
+            },
+        )
+        .map_err(|_| "wrong password or corrupted key file")?;
+    let keypair = Keypair::from_bytes(&secret).map_err(|_| "malformed key file")?;
+    if keypair.pubkey() != pubkey {
+        return Err("secret key does not match pubkey".into());
+    }
+    Ok(keypair)
+}
+
+fn derive_cipher(password: &str, salt: &[u8], log_n: u8, r: u32, p: u32) -> Result<Aes256Gcm, OreCliError> {
+    let params = scrypt::Params::new(log_n, r, p, 32)
+        .map_err(|err| OreCliError::Keypair(format!("invalid scrypt parameters: {}", err)))?;
+    let mut key = [0; 32];
+    scrypt::scrypt(password.as_bytes(), salt, &params, &mut key)
+        .map_err(|err| OreCliError::Keypair(format!("failed to derive key: {}", err)))?;
+    Aes256Gcm::new_from_slice(&key).map_err(|err| OreCliError::Keypair(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use solana_sdk::signature::{Keypair, Signer};
+
+    use super::{decrypt_key, encrypt_key, list_keys};
+
+    #[test]
+    fn encrypted_keys_round_trip() {
+        let keypair = Keypair::new();
+        // A low scrypt cost keeps the test fast
+        let mut contents = encrypt_key(&keypair, "hunter2", 4).unwrap();
+        assert_eq!(contents["pubkey"], keypair.pubkey().to_string());
+        let decrypted = decrypt_key(&contents, "hunter2").unwrap();
+        assert_eq!(decrypted.to_bytes(), keypair.to_bytes());
+        assert!(decrypt_key(&contents, "hunter3").is_err());
+
+        // The pubkey is authenticated along with the secret key
+        contents["pubkey"] = Keypair::new().pubkey().to_string().into();
+        assert!(decrypt_key(&contents, "hunter2").is_err());
+    }
+
+    #[test]
+    fn rejects_excessive_scrypt_costs() {
+        let keypair = Keypair::new();
+        let mut contents = encrypt_key(&keypair, "hunter2", 4).unwrap();
+        contents["kdf"]["log_n"] = 300.into();
+        assert!(decrypt_key(&contents, "hunter2").is_err());
+        contents["kdf"]["log_n"] = 40.into();
+        assert!(decrypt_key(&contents, "hunter2").is_err());
+        contents["kdf"]["log_n"] = 4.into();
+        contents["kdf"]["r"] = (1u64 << 32).into();
+        assert!(decrypt_key(&contents, "hunter2").is_err());
+        contents["kdf"]["r"] = 16.into();
+        contents["kdf"]["p"] = (1u64 << 26).into();
+        assert!(decrypt_key(&contents, "hunter2").is_err());
+    }
+
+    #[test]
+    fn lists_keys_past_malformed_files() {
+        let dir = std::env::temp_dir().join(format!("ore-cli-keystore-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let keypair = Keypair::new();
+        let contents = encrypt_key(&keypair, "hunter2", 4).unwrap();
+        fs::write(dir.join("miner.json"), contents.to_string()).unwrap();
+        fs::write(dir.join("broken.json"), "{").unwrap();
+        fs::write(dir.join("notes.txt"), "not a key").unwrap();
+        let keys = list_keys(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(keys, vec![("miner".to_string(), keypair.pubkey())]);
+    }
+}
@@ -1,29 +1,58 @@
+#[cfg(unix)]
+mod agent;
 mod balance;
+mod bench;
+mod bundle;
 mod busses;
 mod claim;
 mod cu_limits;
+mod error;
 #[cfg(feature = "admin")]
 mod initialize;
+mod keys;
 mod mine;
+mod output;
+mod pool;
+mod priority_fee;
+mod proof;
 mod register;
 mod rewards;
+mod rpc;
 mod send_and_confirm;
+mod signer;
+mod telemetry;
+#[cfg(test)]
+mod test_utils;
+mod tip;
 mod treasury;
 #[cfg(feature = "admin")]
 mod update_admin;
 #[cfg(feature = "admin")]
 mod update_difficulty;
 mod utils;
+mod verify;
 
-use std::sync::Arc;
+use std::{str::FromStr, sync::Arc, time::Duration};
 
+use bundle::BundleClient;
+use busses::BusStrategy;
+use priority_fee::{PriorityFee, PriorityFeeConfig};
+use rpc::{RpcEndpoints, RpcSettings};
+use signer::{LazySigner, SignerConfig, SignerSource};
 use clap::{command, Parser, Subcommand};
-use solana_sdk::signature::{read_keypair_file, Keypair};
+use output::OutputFormat;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
+use tip::TipConfig;
 
 struct Miner {
-    pub keypair_filepath: Option<String>,
-    pub priority_fee: u64,
-    pub cluster: String,
+    pub signers: SignerConfig,
+    pub priority_fee: PriorityFeeConfig,
+    pub rpc: Arc<RpcEndpoints>,
+    pub websocket_url: Option<String>,
+    pub tip: TipConfig,
+    pub bundle: Option<BundleClient>,
+    pub output: OutputFormat,
 }
 
 #[derive(Parser, Debug)]
@@ -32,10 +61,51 @@ struct Args {
     #[arg(
         long,
         value_name = "NETWORK_URL",
-        help = "Network address of your RPC provider",
+        help = "Network address of your RPC provider. May be repeated to broadcast transactions to several providers.",
         global = true
     )]
-    rpc: Option<String>,
+    rpc: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Confirm transactions through a websocket subscription instead of polling",
+        global = true
+    )]
+    websocket: bool,
+
+    #[arg(
+        long,
+        value_name = "WEBSOCKET_URL",
+        help = "Websocket address of your RPC provider. Defaults to one derived from the RPC address. Implies --websocket.",
+        global = true
+    )]
+    ws_url: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "COMMITMENT_LEVEL",
+        help = "Commitment level for reads and confirmations: processed, confirmed or finalized. Defaults to the config file's commitment.",
+        global = true
+    )]
+    commitment: Option<CommitmentLevel>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Number of seconds to wait for an RPC response",
+        default_value = "30",
+        global = true
+    )]
+    rpc_timeout: u64,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Number of seconds to keep idle RPC connections open for reuse",
+        default_value = "90",
+        global = true
+    )]
+    rpc_keep_alive: u64,
 
     #[clap(
         global = true,
@@ -48,20 +118,87 @@ struct Args {
 
     #[arg(
         long,
-        value_name = "KEYPAIR_FILEPATH",
-        help = "Filepath to keypair to use",
+        value_name = "SIGNER",
+        help = "Signer to use: a keypair filepath, env:VAR, seed:VAR[?key=A/C], prompt:[?key=A/C], keystore:NAME or agent:SOCKET[?pubkey=PUBKEY]",
         global = true
     )]
     keypair: Option<String>,
 
+    #[arg(
+        long,
+        value_name = "SIGNER",
+        help = "Signer that pays transaction fees, tips and rent, in the same forms as --keypair. Defaults to the mining keypair.",
+        global = true
+    )]
+    fee_payer: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "MICROLAMPORTS",
+        help = "Number of microlamports to pay as priority fee per transaction, or auto[:PERCENTILE] to use a percentile of recent fees",
+        default_value = "0",
+        global = true
+    )]
+    priority_fee: PriorityFee,
+
     #[arg(
         long,
         value_name = "MICROLAMPORTS",
-        help = "Number of microlamports to pay as priority fee per transaction",
+        help = "Maximum number of microlamports to pay as an automatic or escalated priority fee",
+        global = true
+    )]
+    max_priority_fee: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "MULTIPLIER",
+        help = "Factor to raise the priority fee by each time a transaction is retried. Fees below 1000 microlamports are raised from 1000.",
+        default_value = "1.5",
+        global = true
+    )]
+    priority_fee_multiplier: f64,
+
+    #[arg(
+        long,
+        value_name = "LAMPORTS",
+        help = "Number of lamports to tip per transaction",
         default_value = "0",
         global = true
     )]
-    priority_fee: u64,
+    tip_lamports: u64,
+
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        help = "Account to send tips to. May be repeated. Defaults to the built-in tip accounts.",
+        global = true
+    )]
+    tip_account: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "LAMPORTS",
+        help = "Maximum number of lamports to spend on tips per hour",
+        global = true
+    )]
+    max_tip_per_hour: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "Block engine endpoint to submit mine transactions to as bundles",
+        global = true
+    )]
+    bundle_url: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        help = "Output format of the balance, busses, proof, rewards, treasury and verify commands",
+        default_value = "text",
+        global = true
+    )]
+    output: OutputFormat,
 
     #[command(subcommand)]
     command: Commands,
@@ -69,24 +206,40 @@ struct Args {
 
 #[derive(Subcommand, Debug)]
 enum Commands {
+    #[cfg(unix)]
+    #[command(about = "Hold keys in memory and sign allowed transactions for other processes")]
+    Agent(AgentArgs),
+
     #[command(about = "Fetch the Ore balance of an account")]
     Balance(BalanceArgs),
 
+    #[command(about = "Benchmark local hashing throughput without an RPC connection")]
+    Bench(BenchArgs),
+
     #[command(about = "Fetch the distributable rewards of the busses")]
     Busses(BussesArgs),
 
+    #[command(about = "Manage keys in the encrypted keystore")]
+    Keys(KeysArgs),
+
     #[command(about = "Mine Ore using local compute")]
     Mine(MineArgs),
 
     #[command(about = "Claim available mining rewards")]
     Claim(ClaimArgs),
 
+    #[command(about = "Fetch the proof account of a miner")]
+    Proof(ProofArgs),
+
     #[command(about = "Fetch your balance of unclaimed mining rewards")]
     Rewards(RewardsArgs),
 
     #[command(about = "Fetch the treasury account and balance")]
     Treasury(TreasuryArgs),
 
+    #[command(about = "Check whether a nonce solves a challenge, without an RPC connection")]
+    Verify(VerifyArgs),
+
     #[cfg(feature = "admin")]
     #[command(about = "Initialize the program")]
     Initialize(InitializeArgs),
@@ -100,6 +253,20 @@ enum Commands {
     UpdateDifficulty(UpdateDifficultyArgs),
 }
 
+#[cfg(unix)]
+#[derive(Parser, Debug)]
+struct AgentArgs {
+    #[arg(long, value_name = "PATH", help = "Path of the Unix socket to listen on")]
+    socket: String,
+
+    #[arg(
+        long,
+        value_name = "FILEPATH",
+        help = "YAML file listing the signer of each key to hold and the instruction types it may sign"
+    )]
+    keys: String,
+}
+
 #[derive(Parser, Debug)]
 struct BalanceArgs {
     #[arg(
@@ -110,9 +277,46 @@ struct BalanceArgs {
     pub address: Option<String>,
 }
 
+#[derive(Parser, Debug)]
+struct BenchArgs {
+    #[arg(
+        long,
+        short,
+        value_name = "THREAD_COUNT",
+        help = "The maximum number of threads to benchmark. Defaults to the number of available cores.",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    threads: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "The number of seconds to hash at each thread count",
+        default_value = "5"
+    )]
+    duration: u64,
+
+    #[arg(
+        long,
+        value_name = "NONCE_COUNT",
+        help = "The number of nonces to hash at each thread count. Overrides the duration."
+    )]
+    nonces: Option<u64>,
+}
+
 #[derive(Parser, Debug)]
 struct BussesArgs {}
 
+#[derive(Parser, Debug)]
+struct ProofArgs {
+    #[arg(
+        // long,
+        value_name = "ADDRESS",
+        help = "The address of the miner to fetch the proof of"
+    )]
+    pub address: Option<String>,
+}
+
 #[derive(Parser, Debug)]
 struct RewardsArgs {
     #[arg(
@@ -123,6 +327,69 @@ struct RewardsArgs {
     pub address: Option<String>,
 }
 
+#[derive(Parser, Debug)]
+struct KeysArgs {
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Keystore directory. Defaults to ~/.config/ore/keystore.",
+        global = true
+    )]
+    keystore: Option<String>,
+
+    #[command(subcommand)]
+    command: KeysCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum KeysCommand {
+    #[command(about = "Generate a new key in the keystore")]
+    New(KeysNewArgs),
+
+    #[command(about = "Encrypt an existing key into the keystore")]
+    Import(KeysImportArgs),
+
+    #[command(about = "Decrypt a key from the keystore")]
+    Export(KeysExportArgs),
+
+    #[command(about = "List the keys in the keystore")]
+    List(KeysListArgs),
+}
+
+#[derive(Parser, Debug)]
+struct KeysNewArgs {
+    #[arg(value_name = "NAME", help = "Name of the new key")]
+    name: String,
+}
+
+#[derive(Parser, Debug)]
+struct KeysImportArgs {
+    #[arg(value_name = "NAME", help = "Name of the new key")]
+    name: String,
+
+    #[arg(
+        value_name = "SIGNER",
+        help = "Key to import: a keypair filepath, env:VAR, seed:VAR[?key=A/C] or prompt:[?key=A/C]"
+    )]
+    signer: String,
+}
+
+#[derive(Parser, Debug)]
+struct KeysExportArgs {
+    #[arg(value_name = "NAME", help = "Name of the key to export")]
+    name: String,
+
+    #[arg(
+        long,
+        value_name = "FILEPATH",
+        help = "Keypair file to write the key to. Prints the base58 secret key if omitted."
+    )]
+    outfile: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+struct KeysListArgs {}
+
 #[derive(Parser, Debug)]
 struct MineArgs {
     #[arg(
@@ -130,9 +397,75 @@ struct MineArgs {
         short,
         value_name = "THREAD_COUNT",
         help = "The number of threads to dedicate to mining",
-        default_value = "1"
+        default_value = "1",
+        value_parser = clap::value_parser!(u64).range(1..)
     )]
     threads: u64,
+
+    #[arg(
+        long,
+        value_name = "STRATEGY",
+        help = "How to choose the bus to submit solutions to",
+        default_value = "weighted"
+    )]
+    bus_strategy: BusStrategy,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Directory of keypair files to mine with at once, sharing the mining threads. Overrides --keypair and the config file's keypair_paths."
+    )]
+    keypair_dir: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "JSONL file to append every submitted solution and its result to, for use with `ore verify --file`"
+    )]
+    submission_log: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+struct VerifyArgs {
+    #[arg(
+        long,
+        value_name = "HASH",
+        help = "The challenge hash of the proof",
+        required_unless_present = "file"
+    )]
+    challenge: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        help = "The authority of the proof",
+        required_unless_present = "file"
+    )]
+    authority: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "NONCE",
+        help = "The nonce submitted as a solution",
+        required_unless_present = "file"
+    )]
+    nonce: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "HASH",
+        help = "The difficulty the hash must satisfy",
+        required_unless_present = "file"
+    )]
+    difficulty: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "JSONL file of past submissions to verify, such as one written by `ore mine --submission-log`",
+        conflicts_with_all = ["challenge", "authority", "nonce", "difficulty"]
+    )]
+    file: Option<String>,
 }
 
 #[derive(Parser, Debug)]
@@ -185,64 +518,171 @@ async fn main() {
         solana_cli_config::Config::default()
     };
 
-    // Initialize miner.
-    let cluster = args.rpc.unwrap_or(cli_config.json_rpc_url);
-    let default_keypair = args.keypair.unwrap_or(cli_config.keypair_path);
+    // Initialize miner. Reads go to the first RPC address; transactions go to all of them.
+    let config_file = args
+        .config_file
+        .as_ref()
+        .or(solana_cli_config::CONFIG_FILE.as_ref());
+    let rpc_urls = if args.rpc.is_empty() {
+        let mut urls = vec![cli_config.json_rpc_url];
+        if let Some(config_file) = config_file {
+            for url in rpc::config_file_urls(config_file) {
+                if !urls.contains(&url) {
+                    urls.push(url);
+                }
+            }
+        }
+        urls
+    } else {
+        args.rpc
+    };
+    // Keypairs to mine with, unless a keypair is given on the command line
+    let config_keypairs = match (&args.keypair, config_file) {
+        (None, Some(config_file)) => utils::config_file_list(config_file, "keypair_paths"),
+        _ => vec![],
+    };
+    let parse_signer = |spec: &str| {
+        SignerSource::from_str(spec).unwrap_or_else(|err| {
+            eprintln!("error: {}", err);
+            std::process::exit(err.exit_code());
+        })
+    };
+    let config_keypairs: Vec<SignerSource> = config_keypairs
+        .iter()
+        .map(|spec| parse_signer(spec))
+        .collect();
+    let default_keypair = parse_signer(&args.keypair.unwrap_or(cli_config.keypair_path));
+    let fee_payer = args.fee_payer.as_deref().map(parse_signer);
+    let websocket_url = if args.websocket || args.ws_url.is_some() {
+        Some(args.ws_url.unwrap_or_else(|| {
+            solana_cli_config::Config::compute_websocket_url(&rpc_urls[0])
+        }))
+    } else {
+        None
+    };
 
-    let miner = Arc::new(Miner::new(
-        cluster.clone(),
+    let tip_accounts = args
+        .tip_account
+        .iter()
+        .map(|address| {
+            utils::parse_address(address).unwrap_or_else(|err| {
+                eprintln!("error: {}", err);
+                std::process::exit(err.exit_code());
+            })
+        })
+        .collect();
+    let tip = TipConfig::new(args.tip_lamports, tip_accounts, args.max_tip_per_hour);
+    let priority_fee = PriorityFeeConfig::new(
         args.priority_fee,
-        Some(default_keypair),
+        args.max_priority_fee,
+        args.priority_fee_multiplier,
+    );
+
+    let commitment = args.commitment.unwrap_or_else(|| {
+        CommitmentLevel::from_str(&cli_config.commitment).unwrap_or(CommitmentLevel::Confirmed)
+    });
+    let rpc_settings = RpcSettings {
+        commitment: CommitmentConfig { commitment },
+        timeout: Duration::from_secs(args.rpc_timeout),
+        keep_alive: Duration::from_secs(args.rpc_keep_alive),
+    };
+
+    let miner = Arc::new(Miner::new(
+        RpcEndpoints::new(rpc_urls, rpc_settings),
+        websocket_url,
+        priority_fee,
+        SignerConfig {
+            authority: Some(LazySigner::new(default_keypair.clone())),
+            fee_payer: fee_payer.map(LazySigner::new),
+        },
+        tip,
+        args.bundle_url.map(BundleClient::new),
+        args.output,
     ));
 
     // Execute user command.
-    match args.command {
-        Commands::Balance(args) => {
-            miner.balance(args.address).await;
-        }
-        Commands::Busses(_) => {
-            miner.busses().await;
-        }
-        Commands::Rewards(args) => {
-            miner.rewards(args.address).await;
-        }
-        Commands::Treasury(_) => {
-            miner.treasury().await;
+    let result = match args.command {
+        #[cfg(unix)]
+        Commands::Agent(args) => miner.agent(args.socket, args.keys),
+        Commands::Balance(args) => miner.balance(args.address).await,
+        Commands::Bench(args) => {
+            let threads = args.threads.unwrap_or_else(|| {
+                std::thread::available_parallelism().map_or(1, |n| n.get() as u64)
+            });
+            miner.bench(threads, args.duration, args.nonces);
+            Ok(())
         }
+        Commands::Busses(_) => miner.busses().await,
+        Commands::Proof(args) => miner.proof(args.address).await,
+        Commands::Rewards(args) => miner.rewards(args.address).await,
+        Commands::Treasury(_) => miner.treasury().await,
+        Commands::Keys(args) => match args.command {
+            KeysCommand::New(command) => miner.keys_new(args.keystore, command.name),
+            KeysCommand::Import(command) => {
+                miner.keys_import(args.keystore, command.name, parse_signer(&command.signer))
+            }
+            KeysCommand::Export(command) => {
+                miner.keys_export(args.keystore, command.name, command.outfile)
+            }
+            KeysCommand::List(_) => miner.keys_list(args.keystore),
+        },
         Commands::Mine(args) => {
-            miner.mine(args.threads).await;
+            let keypairs = match args.keypair_dir {
+                Some(dir) => utils::keypair_dir_paths(&dir)
+                    .map(|paths| paths.into_iter().map(SignerSource::File).collect()),
+                None if !config_keypairs.is_empty() => Ok(config_keypairs),
+                None => Ok(vec![default_keypair]),
+            };
+            match keypairs {
+                Ok(keypairs) => {
+                    miner
+                        .mine(keypairs, args.threads, args.bus_strategy, args.submission_log)
+                        .await
+                }
+                Err(err) => Err(err),
+            }
         }
-        Commands::Claim(args) => {
-            miner.claim(cluster, args.beneficiary, args.amount).await;
+        Commands::Verify(args) => {
+            verify::parse_solution(args.challenge, args.authority, args.nonce, args.difficulty)
+                .and_then(|solution| miner.verify(solution, args.file))
         }
+        Commands::Claim(args) => miner.claim(args.beneficiary, args.amount).await,
         #[cfg(feature = "admin")]
-        Commands::Initialize(_) => {
-            miner.initialize().await;
-        }
+        Commands::Initialize(_) => miner.initialize().await,
         #[cfg(feature = "admin")]
-        Commands::UpdateAdmin(args) => {
-            miner.update_admin(args.new_admin).await;
-        }
+        Commands::UpdateAdmin(args) => miner.update_admin(args.new_admin).await,
         #[cfg(feature = "admin")]
-        Commands::UpdateDifficulty(_) => {
-            miner.update_difficulty().await;
-        }
+        Commands::UpdateDifficulty(_) => miner.update_difficulty().await,
+    };
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        std::process::exit(err.exit_code());
     }
 }
 
 impl Miner {
-    pub fn new(cluster: String, priority_fee: u64, keypair_filepath: Option<String>) -> Self {
+    pub fn new(
+        rpc: RpcEndpoints,
+        websocket_url: Option<String>,
+        priority_fee: PriorityFeeConfig,
+        signers: SignerConfig,
+        tip: TipConfig,
+        bundle: Option<BundleClient>,
+        output: OutputFormat,
+    ) -> Self {
         Self {
-            keypair_filepath,
+            signers,
             priority_fee,
-            cluster,
+            rpc: Arc::new(rpc),
+            websocket_url,
+            tip,
+            bundle,
+            output,
         }
     }
 
-    pub fn signer(&self) -> Keypair {
-        match self.keypair_filepath.clone() {
-            Some(filepath) => read_keypair_file(filepath).unwrap(),
-            None => panic!("No keypair provided"),
-        }
+    /// Returns the shared client of the RPC endpoint to send reads to.
+    pub fn rpc_client(&self) -> Arc<RpcClient> {
+        self.rpc.client()
     }
 }
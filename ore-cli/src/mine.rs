@@ -1,181 +1,449 @@
 use std::{
     io::{stdout, Write},
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
 };
 
-use ore::{self, state::Bus, BUS_ADDRESSES, BUS_COUNT, EPOCH_DURATION};
-use rand::Rng;
-use solana_client::nonblocking::rpc_client::RpcClient;
+use futures::future::try_join_all;
+use ore::{
+    self,
+    error::OreError,
+    state::{Bus, Proof, Treasury},
+    utils::AccountDeserialize,
+    BUS_ADDRESSES, EPOCH_DURATION, TREASURY_ADDRESS,
+};
 use solana_sdk::{
-    commitment_config::CommitmentConfig,
     compute_budget::ComputeBudgetInstruction,
     keccak::{hashv, Hash as KeccakHash},
     pubkey::Pubkey,
     signature::Signer,
-    system_instruction,
-    transaction::Transaction,
 };
+use spl_token::amount_to_ui_amount;
 
 use crate::{
+    busses::{select_bus, BusStrategy},
     cu_limits::{CU_LIMIT_MINE, CU_LIMIT_RESET},
-    utils::{get_clock_account, get_proof, get_treasury},
+    error::OreCliError,
+    pool::HashPool,
+    rpc::{backoff_delay, RpcEndpoints},
+    telemetry::{format_hashrate, HashrateSnapshot},
+    signer::{SharedSigner, SignerSource},
+    utils::{get_mining_state, proof_pubkey, MiningState},
+    verify::{log_submission, Solution},
     Miner,
 };
 
-// Define the tip accounts as constants in your module
-const TIP_ACCOUNTS: &[Pubkey] = &[
-    Pubkey::from_str("96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5").unwrap(),
-    Pubkey::from_str("HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe").unwrap(),
-    Pubkey::from_str("Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY").unwrap(),
-    Pubkey::from_str("ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49").unwrap(),
-    Pubkey::from_str("DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh").unwrap(),
-    Pubkey::from_str("ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt").unwrap(),
-    Pubkey::from_str("DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL").unwrap(),
-    Pubkey::from_str("3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT").unwrap(),
-];
+// Interval at which the proof and treasury are polled for a new challenge
+const CHALLENGE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// Number of times a solution is submitted while the program rejects it with a recoverable error
+const SUBMIT_ATTEMPTS: usize = 4;
+
+// Interval at which the stats of every wallet are printed when mining with several
+const STATS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A mining authority and its results since the miner started.
+struct Wallet {
+    signer: SharedSigner,
+    stats: Mutex<WalletStats>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct WalletStats {
+    /// Hashes per second of the latest search.
+    hashrate: f64,
+
+    /// Ore in the authority's token account.
+    balance: u64,
+
+    /// Ore the authority can claim.
+    claimable_rewards: u64,
+
+    /// Number of solutions found.
+    solutions: u64,
+
+    /// Number of solutions that landed.
+    landed: u64,
+
+    /// Number of solutions that could not be landed.
+    failed: u64,
+}
 
 impl Miner {
-    pub async fn mine(&self, threads: u64) {
-        let signer = self.signer();
-        self.register().await;
+    /// Mines with the signer from each source in `keypairs`. Their challenge searches share one
+    /// pool of `threads` hashing threads.
+    pub async fn mine(
+        &self,
+        keypairs: Vec<SignerSource>,
+        threads: u64,
+        bus_strategy: BusStrategy,
+        submission_log: Option<String>,
+    ) -> Result<(), OreCliError> {
+        let wallets = keypairs
+            .iter()
+            .map(|source| {
+                Ok(Arc::new(Wallet {
+                    signer: source.load()?,
+                    stats: Mutex::new(WalletStats::default()),
+                }))
+            })
+            .collect::<Result<Vec<_>, OreCliError>>()?;
+        for wallet in &wallets {
+            self.register(wallet.signer.as_ref()).await?;
+        }
+        tokio::spawn(self.rpc.clone().monitor());
+
+        // A single wallet reports its own progress. Several are summarized in a table instead.
+        let pool = HashPool::new(threads);
+        let verbose = wallets.len() == 1;
+        let miners = try_join_all(wallets.iter().map(|wallet| {
+            self.mine_wallet(wallet.clone(), &pool, bus_strategy, submission_log.as_deref(), verbose)
+        }));
+        if verbose {
+            miners.await?;
+        } else {
+            tokio::select! {
+                result = miners => { result?; }
+                _ = self.report_stats(&wallets) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Mines with `wallet` forever, submitting each solution it finds.
+    async fn mine_wallet(
+        &self,
+        wallet: Arc<Wallet>,
+        pool: &HashPool,
+        bus_strategy: BusStrategy,
+        submission_log: Option<&str>,
+        verbose: bool,
+    ) -> Result<(), OreCliError> {
+        let signer = wallet.signer.as_ref();
         let mut stdout = stdout();
         let mut rng = rand::thread_rng();
+        let mut rpc_errors = 0;
 
-        while let Ok(current_hash) = self.rpc_client.get_latest_blockhash().await {
-            // Fetch account state
-            let balance = self.get_ore_display_balance().await;
-            let treasury = get_treasury(self.cluster.clone()).await;
-            let proof = get_proof(self.cluster.clone(), signer.pubkey()).await;
-            let rewards = (proof.claimable_rewards as f64) / (10f64.powf(ore::TOKEN_DECIMALS as f64));
+        loop {
+            // Fetch account state, backing off while the RPC is unavailable
+            let Some(state) = self.fetch_mining_state(Some(signer.pubkey()), &mut rpc_errors).await else {
+                continue;
+            };
+            let Some(proof) = state.proof else {
+                println!("Proof account of {} not found, retrying...", signer.pubkey());
+                tokio::time::sleep(backoff_delay(1)).await;
+                continue;
+            };
+            let treasury = state.treasury;
+            {
+                let mut stats = wallet.stats.lock().expect("failed to lock mutex");
+                stats.balance = state.ata.map_or(0, |ata| ata.amount);
+                stats.claimable_rewards = proof.claimable_rewards;
+            }
+            if verbose {
+                let balance = amount_to_ui_amount(state.ata.map_or(0, |ata| ata.amount), ore::TOKEN_DECIMALS);
+                let rewards = amount_to_ui_amount(proof.claimable_rewards, ore::TOKEN_DECIMALS);
+                let time_left = treasury.last_reset_at + EPOCH_DURATION - state.clock.unix_timestamp;
+                stdout.write_all(b"\x1b[2J\x1b[3J\x1b[H").ok();
+                println!("Balance: {} ORE, Claimable: {} ORE, Mining for a valid hash...", balance, rewards);
+                if time_left > 0 {
+                    println!("Epoch ends in {}s", time_left);
+                } else {
+                    println!("Epoch ended {}s ago, awaiting reset", -time_left);
+                }
+                if self.rpc.len() > 1 {
+                    print!("{}", self.rpc);
+                }
+            }
 
-            stdout.write_all(b"\x1b[2J\x1b[3J\x1b[H").ok();
-            println!("Balance: {} ORE, Claimable: {} ORE, Mining for a valid hash...", balance, rewards);
+            // Hash until a solution is found or the challenge goes stale
+            let hash: KeccakHash = proof.hash.into();
+            let difficulty: KeccakHash = treasury.difficulty.into();
+            let pubkey = signer.pubkey();
+            let stop = Arc::new(AtomicBool::new(false));
+            tokio::spawn(watch_challenge(
+                self.rpc.clone(),
+                pubkey,
+                hash,
+                difficulty,
+                stop.clone(),
+            ));
+            let search = pool.search(hash, pubkey, difficulty, stop, None);
+            let search = tokio::task::spawn_blocking({
+                let wallet = wallet.clone();
+                move || {
+                    search.wait(|stats| {
+                        wallet.stats.lock().expect("failed to lock mutex").hashrate = stats.hashrate;
+                        if verbose {
+                            let mut stdout = std::io::stdout();
+                            stdout.write_all(format!("\r\x1b[K{}", stats).as_bytes()).ok();
+                            stdout.flush().ok();
+                        }
+                    })
+                }
+            });
+            let Ok((solution, stats)) = search.await else {
+                continue;
+            };
+            let Some((next_hash, nonce)) = solution else {
+                if verbose {
+                    println!("\r\x1b[KChallenge changed after {}, restarting search...", stats);
+                }
+                continue;
+            };
+            if verbose {
+                println!("\r\x1b[KFound {} after {}", next_hash, stats);
+            }
+            wallet.stats.lock().expect("failed to lock mutex").solutions += 1;
 
-            let (next_hash, nonce) = self.find_next_hash_par(proof.hash.into(), treasury.difficulty.into(), threads);
+            // Submit the solution, resetting the epoch or switching bus when the program asks to
+            let mut drained_busses = vec![];
+            let mut landed = false;
+            let mut reset_failures = 0;
+            for _ in 0..SUBMIT_ATTEMPTS {
+                // Reset the epoch if it ended while hashing, then pick a bus that can pay out
+                let bus = loop {
+                    let Some(state) = self.fetch_mining_state(None, &mut rpc_errors).await else {
+                        continue;
+                    };
+                    if let Some(result) = self.reset_if_needed(&state, signer).await {
+                        match result {
+                            Ok(()) => reset_failures = 0,
+                            // No reset or submission can land without a working signer and SOL
+                            Err(err @ (OreCliError::Keypair(_) | OreCliError::InsufficientFunds)) => return Err(err),
+                            Err(_) => {
+                                reset_failures += 1;
+                                tokio::time::sleep(backoff_delay(reset_failures)).await;
+                            }
+                        }
+                        continue;
+                    }
+                    let busses: Vec<Bus> = state
+                        .busses
+                        .into_iter()
+                        .filter(|bus| !drained_busses.contains(&bus.id))
+                        .collect();
+                    match select_bus(&busses, state.treasury.reward_rate, bus_strategy, &mut rng) {
+                        Some(bus) => break bus,
+                        None => {
+                            let time_left = (state.treasury.last_reset_at + EPOCH_DURATION - state.clock.unix_timestamp).max(1);
+                            println!("All busses are drained, waiting {}s for the next epoch...", time_left);
+                            tokio::time::sleep(Duration::from_secs(time_left as u64)).await;
+                            drained_busses.clear();
+                        }
+                    }
+                };
 
-            // Create mining and tip transactions
-            let tip_index = rng.gen_range(0..TIP_ACCOUNTS.len());
-            let tip_pubkey = Pubkey::from_str(TIP_ACCOUNTS[tip_index]).unwrap();
-            let tip_tx = system_instruction::transfer(&signer.pubkey(), &tip_pubkey, 1_000_000_000); // Tip 1 SOL
+                let cu_limit_ix = ComputeBudgetInstruction::set_compute_unit_limit(CU_LIMIT_MINE);
+                let mining_ix = ore::instruction::mine(signer.pubkey(), BUS_ADDRESSES[bus.id as usize], next_hash.into(), nonce);
+                let result = self.send_and_confirm_bundle(signer, &[cu_limit_ix, mining_ix]).await;
+                if let Some(path) = submission_log {
+                    let solution = Solution {
+                        challenge: hash,
+                        authority: pubkey,
+                        nonce,
+                        difficulty,
+                    };
+                    log_submission(path, &solution, bus.id, &result);
+                }
+                match result {
+                    Ok(signature) => {
+                        println!("Transaction submitted successfully: {}", signature);
+                        landed = true;
+                        break;
+                    }
+                    Err(err @ OreCliError::Ore(OreError::NeedsReset)) => {
+                        println!("{}", err);
+                        self.reset(signer).await.ok();
+                    }
+                    Err(err @ OreCliError::Ore(OreError::BusRewardsInsufficient)) => {
+                        println!("{}", err);
+                        drained_busses.push(bus.id);
+                    }
+                    // Any other error, including a stale challenge, means the solution can't land,
+                    // so mine again against a freshly fetched proof
+                    Err(err) => {
+                        println!("Failed to submit transaction: {}", err);
+                        break;
+                    }
+                }
+            }
+            let mut stats = wallet.stats.lock().expect("failed to lock mutex");
+            if landed {
+                stats.landed += 1;
+            } else {
+                stats.failed += 1;
+            }
+        }
+    }
 
-            let mining_ix = ore::instruction::mine(signer.pubkey(), BUS_ADDRESSES[rng.gen_range(0..BUS_COUNT)], next_hash, nonce);
-            let mining_tx = Transaction::new_signed_with_payer(
-                &[mining_ix, tip_tx],
-                Some(&signer.pubkey()),
-                &[&signer],
-                current_hash,
+    /// Prints a table of the stats of every wallet at a fixed interval, forever.
+    async fn report_stats(&self, wallets: &[Arc<Wallet>]) {
+        let mut stdout = stdout();
+        loop {
+            tokio::time::sleep(STATS_INTERVAL).await;
+            let stats: Vec<WalletStats> = wallets
+                .iter()
+                .map(|wallet| wallet.stats.lock().expect("failed to lock mutex").clone())
+                .collect();
+            stdout.write_all(b"\x1b[2J\x1b[3J\x1b[H").ok();
+            println!(
+                "{:<44} {:>10} {:>14} {:>14} {:>9} {:>6} {:>6}",
+                "Authority", "Hashrate", "Balance", "Claimable", "Solutions", "Landed", "Failed"
             );
-
-            match self.rpc_client.send_and_confirm_transaction(&mining_tx).await {
-                Ok(signature) => println!("Transaction submitted successfully: {}", signature),
-                Err(e) => println!("Failed to submit transaction: {}", e),
+            for (wallet, stats) in wallets.iter().zip(&stats) {
+                println!(
+                    "{:<44} {:>10} {:>14} {:>14} {:>9} {:>6} {:>6}",
+                    wallet.signer.pubkey().to_string(),
+                    format!("{}/s", format_hashrate(stats.hashrate)),
+                    amount_to_ui_amount(stats.balance, ore::TOKEN_DECIMALS),
+                    amount_to_ui_amount(stats.claimable_rewards, ore::TOKEN_DECIMALS),
+                    stats.solutions,
+                    stats.landed,
+                    stats.failed,
+                );
+            }
+            println!(
+                "{:<44} {:>10} {:>14} {:>14} {:>9} {:>6} {:>6}",
+                "Total",
+                format!("{}/s", format_hashrate(stats.iter().map(|s| s.hashrate).sum())),
+                amount_to_ui_amount(stats.iter().map(|s| s.balance).sum(), ore::TOKEN_DECIMALS),
+                amount_to_ui_amount(stats.iter().map(|s| s.claimable_rewards).sum(), ore::TOKEN_DECIMALS),
+                stats.iter().map(|s| s.solutions).sum::<u64>(),
+                stats.iter().map(|s| s.landed).sum::<u64>(),
+                stats.iter().map(|s| s.failed).sum::<u64>(),
+            );
+            if self.rpc.len() > 1 {
+                print!("{}", self.rpc);
             }
         }
     }
 
-
-    fn _find_next_hash(&self, hash: KeccakHash, difficulty: KeccakHash) -> (KeccakHash, u64) {
-        let signer = self.signer();
+    fn _find_next_hash(&self, hash: KeccakHash, authority: Pubkey, difficulty: KeccakHash) -> (KeccakHash, u64) {
         let mut next_hash: KeccakHash;
         let mut nonce = 0u64;
         loop {
-            next_hash = hashv(&[
-                hash.to_bytes().as_slice(),
-                signer.pubkey().to_bytes().as_slice(),
-                nonce.to_le_bytes().as_slice(),
-            ]);
+            next_hash = solution_hash(hash, authority, nonce);
             if next_hash.le(&difficulty) {
                 break;
             } else {
-                println!("Invalid hash: {} Nonce: {:?}", next_hash.to_string(), nonce);
+                println!("Invalid hash: {} Nonce: {:?}", next_hash, nonce);
             }
             nonce += 1;
         }
         (next_hash, nonce)
     }
 
-    fn find_next_hash_par(
-        &self,
-        hash: KeccakHash,
-        difficulty: KeccakHash,
-        threads: u64,
-    ) -> (KeccakHash, u64) {
-        let found_solution = Arc::new(AtomicBool::new(false));
-        let solution = Arc::new(Mutex::<(KeccakHash, u64)>::new((
-            KeccakHash::new_from_array([0; 32]),
-            0,
-        )));
-        let signer = self.signer();
-        let pubkey = signer.pubkey();
-        let thread_handles: Vec<_> = (0..threads)
-            .map(|i| {
-                std::thread::spawn({
-                    let found_solution = found_solution.clone();
-                    let solution = solution.clone();
-                    let mut stdout = stdout();
-                    move || {
-                        let n = u64::MAX.saturating_div(threads).saturating_mul(i);
-                        let mut next_hash: KeccakHash;
-                        let mut nonce: u64 = n;
-                        loop {
-                            next_hash = hashv(&[
-                                hash.to_bytes().as_slice(),
-                                pubkey.to_bytes().as_slice(),
-                                nonce.to_le_bytes().as_slice(),
-                            ]);
-                            if nonce % 10_000 == 0 {
-                                if found_solution.load(std::sync::atomic::Ordering::Relaxed) {
-                                    return;
-                                }
-                                if n == 0 {
-                                    stdout
-                                        .write_all(
-                                            format!("\r{}", next_hash.to_string()).as_bytes(),
-                                        )
-                                        .ok();
-                                }
-                            }
-                            if next_hash.le(&difficulty) {
-                                stdout
-                                    .write_all(format!("\r{}", next_hash.to_string()).as_bytes())
-                                    .ok();
-                                found_solution.store(true, std::sync::atomic::Ordering::Relaxed);
-                                let mut w_solution = solution.lock().expect("failed to lock mutex");
-                                *w_solution = (next_hash, nonce);
-                                return;
-                            }
-                            nonce += 1;
-                        }
-                    }
-                })
-            })
-            .collect();
+    /// Fetches a snapshot of the mining accounts, backing off and returning `None` if the RPC
+    /// request fails. `rpc_errors` counts consecutive failures.
+    async fn fetch_mining_state(&self, authority: Option<Pubkey>, rpc_errors: &mut u32) -> Option<MiningState> {
+        let client = self.rpc_client();
+        match get_mining_state(&client, authority).await {
+            Ok(state) => {
+                *rpc_errors = 0;
+                Some(state)
+            }
+            Err(err) => {
+                *rpc_errors += 1;
+                self.rpc.record_error(&client.url());
+                let delay = backoff_delay(*rpc_errors);
+                println!(
+                    "Error fetching accounts from {}: {}. Retrying in {:.1}s...",
+                    client.url(),
+                    err,
+                    delay.as_secs_f64()
+                );
+                tokio::time::sleep(delay).await;
+                None
+            }
+        }
+    }
 
-        for thread_handle in thread_handles {
-            thread_handle.join().unwrap();
+    /// Submits a reset transaction if the epoch in `state` is over. Returns the result of the
+    /// reset, or `None` if none was needed.
+    async fn reset_if_needed(&self, state: &MiningState, signer: &dyn Signer) -> Option<Result<(), OreCliError>> {
+        if state.treasury.last_reset_at + EPOCH_DURATION > state.clock.unix_timestamp {
+            return None;
         }
+        Some(self.reset(signer).await)
+    }
 
-        let r_solution = solution.lock().expect("Failed to get lock");
-        *r_solution
+    /// Submits a reset transaction. Failures are logged since another miner may have reset the
+    /// epoch first.
+    async fn reset(&self, signer: &dyn Signer) -> Result<(), OreCliError> {
+        let cu_limit_ix = ComputeBudgetInstruction::set_compute_unit_limit(CU_LIMIT_RESET);
+        let ix = ore::instruction::reset(signer.pubkey());
+        println!("Submitting reset transaction...");
+        if let Err(err) = self
+            .send_and_confirm(signer, &[cu_limit_ix, ix], false, false)
+            .await
+        {
+            println!("Reset failed: {}", err);
+            return Err(err);
+        }
+        Ok(())
     }
+}
 
-    pub async fn get_ore_display_balance(&self) -> String {
-        let client =
-            RpcClient::new_with_commitment(self.cluster.clone(), CommitmentConfig::confirmed());
-        let signer = self.signer();
-        let token_account_address = spl_associated_token_account::get_associated_token_address(
-            &signer.pubkey(),
-            &ore::MINT_ADDRESS,
-        );
-        match client.get_token_account(&token_account_address).await {
-            Ok(token_account) => {
-                if let Some(token_account) = token_account {
-                    token_account.token_amount.ui_amount_string
-                } else {
-                    "0.00".to_string()
-                }
-            }
-            Err(_) => "Err".to_string(),
+/// Polls the proof and treasury accounts until `stop` is set, setting it if the challenge hash or
+/// difficulty no longer match the ones being searched.
+async fn watch_challenge(
+    rpc: Arc<RpcEndpoints>,
+    authority: Pubkey,
+    hash: KeccakHash,
+    difficulty: KeccakHash,
+    stop: Arc<AtomicBool>,
+) {
+    let addresses = [proof_pubkey(authority), TREASURY_ADDRESS];
+    while !stop.load(Ordering::Relaxed) {
+        tokio::time::sleep(CHALLENGE_POLL_INTERVAL).await;
+        let client = rpc.client();
+        let Ok(accounts) = client.get_multiple_accounts(&addresses).await else {
+            rpc.record_error(&client.url());
+            continue;
+        };
+        let (Some(proof), Some(treasury)) = (&accounts[0], &accounts[1]) else {
+            continue;
+        };
+        let (Ok(proof), Ok(treasury)) = (
+            Proof::try_from_bytes(&proof.data),
+            Treasury::try_from_bytes(&treasury.data),
+        ) else {
+            continue;
+        };
+        if KeccakHash::from(proof.hash).ne(&hash)
+            || KeccakHash::from(treasury.difficulty).ne(&difficulty)
+        {
+            stop.store(true, Ordering::Relaxed);
         }
     }
-}
\ No newline at end of file
+}
+
+/// Returns the hash of `nonce` for `authority` against the `challenge` hash, as the program
+/// computes it.
+pub fn solution_hash(challenge: KeccakHash, authority: Pubkey, nonce: u64) -> KeccakHash {
+    hashv(&[
+        challenge.to_bytes().as_slice(),
+        authority.to_bytes().as_slice(),
+        nonce.to_le_bytes().as_slice(),
+    ])
+}
+
+/// Searches for a nonce whose hash satisfies `difficulty` across `threads` worker threads,
+/// calling `report` with the search progress until a solution is found, `stop` is set, or
+/// `max_nonces` nonces have been checked.
+pub fn find_next_hash_par(
+    hash: KeccakHash,
+    pubkey: Pubkey,
+    difficulty: KeccakHash,
+    threads: u64,
+    stop: Arc<AtomicBool>,
+    max_nonces: Option<u64>,
+    report: impl FnMut(&HashrateSnapshot),
+) -> (Option<(KeccakHash, u64)>, HashrateSnapshot) {
+    let pool = HashPool::new(threads);
+    pool.search(hash, pubkey, difficulty, stop, max_nonces).wait(report)
+}
@@ -0,0 +1,36 @@
+use serde_json::{json, Value};
+use spl_token::amount_to_ui_amount_string_trimmed;
+
+/// How read commands print their results.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human readable text
+    #[default]
+    Text,
+
+    /// Pretty printed JSON
+    Json,
+
+    /// JSON on a single line
+    JsonCompact,
+}
+
+impl OutputFormat {
+    /// Prints `value` as JSON, returning `false` without printing anything if the format is text.
+    pub fn print_json(self, value: &Value) -> bool {
+        match self {
+            OutputFormat::Text => return false,
+            OutputFormat::Json => println!("{:#}", value),
+            OutputFormat::JsonCompact => println!("{}", value),
+        }
+        true
+    }
+}
+
+/// Returns an Ore amount as its raw integer value and its decimal string.
+pub fn ore_amount(amount: u64) -> Value {
+    json!({
+        "amount": amount,
+        "ui_amount": amount_to_ui_amount_string_trimmed(amount, ore::TOKEN_DECIMALS),
+    })
+}
@@ -0,0 +1,230 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use solana_sdk::{keccak::Hash as KeccakHash, pubkey::Pubkey};
+
+use crate::{
+    mine::solution_hash,
+    telemetry::{HashTelemetry, HashrateSnapshot},
+};
+
+// Number of nonces a worker checks before moving on to the next search
+const NONCE_BATCH: u64 = 10_000;
+
+// Interval at which search progress is reported
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A fixed set of hashing threads shared by every challenge search in the process. Workers take
+/// turns checking a batch of nonces for each running search.
+pub struct HashPool {
+    threads: u64,
+    state: Arc<PoolState>,
+}
+
+struct PoolState {
+    searches: Mutex<Vec<Arc<Search>>>,
+    wakeup: Condvar,
+    closed: AtomicBool,
+}
+
+/// A running search for a nonce whose hash satisfies a difficulty.
+pub struct Search {
+    hash: KeccakHash,
+    authority: Pubkey,
+    difficulty: KeccakHash,
+    stop: Arc<AtomicBool>,
+    max_nonces: Option<u64>,
+    next_nonce: AtomicU64,
+    active_workers: AtomicUsize,
+    solution: Mutex<Option<(KeccakHash, u64)>>,
+    telemetry: HashTelemetry,
+}
+
+impl HashPool {
+    pub fn new(threads: u64) -> Self {
+        let state = Arc::new(PoolState {
+            searches: Mutex::new(vec![]),
+            wakeup: Condvar::new(),
+            closed: AtomicBool::new(false),
+        });
+        for i in 0..threads {
+            let state = state.clone();
+            std::thread::spawn(move || work(i as usize, &state));
+        }
+        Self { threads, state }
+    }
+
+    /// Starts searching for a nonce whose hash of `hash` and `authority` satisfies `difficulty`.
+    /// The search runs until a solution is found, `stop` is set, or `max_nonces` nonces have been
+    /// checked.
+    pub fn search(
+        &self,
+        hash: KeccakHash,
+        authority: Pubkey,
+        difficulty: KeccakHash,
+        stop: Arc<AtomicBool>,
+        max_nonces: Option<u64>,
+    ) -> Arc<Search> {
+        let search = Arc::new(Search {
+            hash,
+            authority,
+            difficulty,
+            stop,
+            max_nonces,
+            next_nonce: AtomicU64::new(0),
+            active_workers: AtomicUsize::new(0),
+            solution: Mutex::new(None),
+            telemetry: HashTelemetry::new(self.threads, difficulty),
+        });
+        let mut searches = self.state.searches.lock().expect("failed to lock mutex");
+        searches.push(search.clone());
+        self.state.wakeup.notify_all();
+        search
+    }
+}
+
+impl Drop for HashPool {
+    fn drop(&mut self) {
+        self.state.closed.store(true, Ordering::Relaxed);
+        let _searches = self.state.searches.lock().expect("failed to lock mutex");
+        self.state.wakeup.notify_all();
+    }
+}
+
+impl Search {
+    /// Blocks until the search ends, calling `report` with its progress. Returns the solution,
+    /// if one was found, and the final progress.
+    pub fn wait(
+        &self,
+        mut report: impl FnMut(&HashrateSnapshot),
+    ) -> (Option<(KeccakHash, u64)>, HashrateSnapshot) {
+        let mut reported_at = Instant::now();
+        while !self.stop.load(Ordering::Relaxed) {
+            std::thread::sleep(Duration::from_millis(1));
+            if reported_at.elapsed() >= PROGRESS_INTERVAL {
+                report(&self.telemetry.snapshot());
+                reported_at = Instant::now();
+            }
+        }
+        // Let batches in progress finish so they are counted in the final progress
+        while self.active_workers.load(Ordering::Acquire) > 0 {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let solution = *self.solution.lock().expect("failed to lock mutex");
+        (solution, self.telemetry.snapshot())
+    }
+
+    /// Checks the next batch of nonces on behalf of worker `i`.
+    fn check_batch(&self, i: usize) {
+        self.active_workers.fetch_add(1, Ordering::Acquire);
+        if self.stop.load(Ordering::Relaxed) {
+            self.active_workers.fetch_sub(1, Ordering::Release);
+            return;
+        }
+        let start = self.next_nonce.fetch_add(NONCE_BATCH, Ordering::Relaxed);
+        let mut end = start.saturating_add(NONCE_BATCH);
+        if let Some(max_nonces) = self.max_nonces {
+            end = end.min(max_nonces);
+            if end >= max_nonces {
+                self.stop.store(true, Ordering::Relaxed);
+            }
+        }
+        let mut checked = end.saturating_sub(start);
+        for nonce in start..end {
+            let next_hash = solution_hash(self.hash, self.authority, nonce);
+            if next_hash.le(&self.difficulty) {
+                checked = nonce - start + 1;
+                let mut solution = self.solution.lock().expect("failed to lock mutex");
+                solution.get_or_insert((next_hash, nonce));
+                self.stop.store(true, Ordering::Relaxed);
+                break;
+            }
+        }
+        self.telemetry.record(i, checked);
+        self.active_workers.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// Runs worker `i`, checking a batch of nonces for each running search in turn until the pool is
+/// dropped.
+fn work(i: usize, state: &PoolState) {
+    let mut turn = i;
+    loop {
+        let search = {
+            let mut searches = state.searches.lock().expect("failed to lock mutex");
+            loop {
+                if state.closed.load(Ordering::Relaxed) {
+                    return;
+                }
+                searches.retain(|search| !search.stop.load(Ordering::Relaxed));
+                if !searches.is_empty() {
+                    break;
+                }
+                searches = state.wakeup.wait(searches).expect("failed to lock mutex");
+            }
+            turn = (turn + 1) % searches.len();
+            searches[turn].clone()
+        };
+        search.check_batch(i);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::AtomicBool, Arc};
+
+    use solana_sdk::{keccak::Hash as KeccakHash, pubkey::Pubkey};
+
+    use super::HashPool;
+    use crate::mine::solution_hash;
+
+    #[test]
+    fn solves_concurrent_searches() {
+        let pool = HashPool::new(2);
+        // Roughly one in 16 hashes satisfies this difficulty
+        let mut difficulty = [0xff; 32];
+        difficulty[0] = 0x0f;
+        let difficulty = KeccakHash::new_from_array(difficulty);
+        let searches: Vec<_> = (0..3)
+            .map(|_| {
+                let authority = Pubkey::new_unique();
+                let search = pool.search(
+                    KeccakHash::new_unique(),
+                    authority,
+                    difficulty,
+                    Arc::new(AtomicBool::new(false)),
+                    None,
+                );
+                (search, authority)
+            })
+            .collect();
+        for (search, authority) in searches {
+            let (solution, _) = search.wait(|_| {});
+            let (hash, nonce) = solution.expect("no solution found");
+            assert!(hash.le(&difficulty));
+            assert_eq!(hash, solution_hash(search.hash, authority, nonce));
+        }
+    }
+
+    #[test]
+    fn stops_after_max_nonces() {
+        let pool = HashPool::new(3);
+        // A zero difficulty is never satisfied
+        let difficulty = KeccakHash::new_from_array([0; 32]);
+        let search = pool.search(
+            KeccakHash::new_unique(),
+            Pubkey::new_unique(),
+            difficulty,
+            Arc::new(AtomicBool::new(false)),
+            Some(25_000),
+        );
+        let (solution, stats) = search.wait(|_| {});
+        assert!(solution.is_none());
+        assert_eq!(stats.total_nonces, 25_000);
+    }
+}
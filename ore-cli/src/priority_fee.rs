@@ -0,0 +1,171 @@
+use std::str::FromStr;
+
+use solana_program::instruction::Instruction;
+
+use crate::Miner;
+
+const DEFAULT_PERCENTILE: u8 = 75;
+
+// Fee that retries escalate from when the previous attempt paid less, so a zero fee still rises
+const MIN_ESCALATION_FEE: u64 = 1_000;
+
+// Maximum number of accounts accepted by getRecentPrioritizationFees
+const MAX_FEE_ACCOUNTS: usize = 128;
+
+/// How to price compute units for submitted transactions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PriorityFee {
+    /// A fixed number of microlamports per compute unit.
+    Fixed(u64),
+
+    /// A percentile of the recent prioritization fees paid to write the same accounts.
+    Auto { percentile: u8 },
+}
+
+/// Priority fee settings shared by every transaction the miner sends.
+pub struct PriorityFeeConfig {
+    /// How to price compute units on the first attempt.
+    pub fee: PriorityFee,
+
+    /// Maximum microlamports per compute unit to pay, whether automatic or escalated.
+    pub max: Option<u64>,
+
+    /// Factor to raise the fee by each time a transaction is retried.
+    pub multiplier: f64,
+}
+
+impl PriorityFeeConfig {
+    pub fn new(fee: PriorityFee, max: Option<u64>, multiplier: f64) -> Self {
+        Self {
+            fee,
+            max,
+            multiplier,
+        }
+    }
+
+    /// Returns the compute unit price to pay when retrying a transaction that paid `fee`.
+    pub fn escalate(&self, fee: u64) -> u64 {
+        let escalated = (fee.max(MIN_ESCALATION_FEE) as f64 * self.multiplier).ceil() as u64;
+        match self.max {
+            Some(max) => escalated.min(max).max(fee),
+            None => escalated.max(fee),
+        }
+    }
+}
+
+impl FromStr for PriorityFee {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(rest) = s.strip_prefix("auto") else {
+            return s
+                .parse()
+                .map(PriorityFee::Fixed)
+                .map_err(|_| format!("invalid priority fee `{}`", s));
+        };
+        let percentile = match rest.strip_prefix(':') {
+            Some(p) => p
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= 100)
+                .ok_or_else(|| format!("invalid percentile `{}`", p))?,
+            None if rest.is_empty() => DEFAULT_PERCENTILE,
+            None => return Err(format!("invalid priority fee `{}`", s)),
+        };
+        Ok(PriorityFee::Auto { percentile })
+    }
+}
+
+impl Miner {
+    /// Returns the compute unit price, in microlamports, to pay for a transaction of `ixs`.
+    /// Recent fees are read from every endpoint in turn before falling back to zero.
+    pub async fn get_priority_fee(&self, ixs: &[Instruction]) -> u64 {
+        let percentile = match self.priority_fee.fee {
+            PriorityFee::Fixed(fee) => return fee,
+            PriorityFee::Auto { percentile } => percentile,
+        };
+        let mut accounts = vec![ore::ID];
+        for ix in ixs {
+            if ix.program_id == ore::ID {
+                accounts.extend(
+                    ix.accounts
+                        .iter()
+                        .filter(|meta| meta.is_writable || meta.pubkey == ore::TREASURY_ADDRESS)
+                        .map(|meta| meta.pubkey),
+                );
+            }
+        }
+        accounts.sort();
+        accounts.dedup();
+        accounts.truncate(MAX_FEE_ACCOUNTS);
+        let accounts = &accounts;
+        let fees = self
+            .rpc
+            .read(|client| async move { Ok(client.get_recent_prioritization_fees(accounts).await?) })
+            .await;
+        let fee = match fees {
+            Ok(fees) => {
+                let mut fees: Vec<u64> = fees.iter().map(|f| f.prioritization_fee).collect();
+                fees.sort_unstable();
+                percentile_of(&fees, percentile)
+            }
+            Err(err) => {
+                println!("Error fetching recent prioritization fees: {}", err);
+                0
+            }
+        };
+        match self.priority_fee.max {
+            Some(max) => fee.min(max),
+            None => fee,
+        }
+    }
+}
+
+/// Returns the value at `percentile` of the sorted `values`, or zero if there are none.
+fn percentile_of(values: &[u64], percentile: u8) -> u64 {
+    if values.is_empty() {
+        return 0;
+    }
+    let i = (values.len() - 1) * percentile as usize / 100;
+    values[i]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{percentile_of, PriorityFee, PriorityFeeConfig};
+
+    #[test]
+    fn parses_priority_fees() {
+        assert_eq!(PriorityFee::from_str("5000"), Ok(PriorityFee::Fixed(5000)));
+        assert_eq!(PriorityFee::from_str("auto"), Ok(PriorityFee::Auto { percentile: 75 }));
+        assert_eq!(PriorityFee::from_str("auto:90"), Ok(PriorityFee::Auto { percentile: 90 }));
+        assert!(PriorityFee::from_str("auto:101").is_err());
+        assert!(PriorityFee::from_str("auto:").is_err());
+        assert!(PriorityFee::from_str("auto90").is_err());
+        assert!(PriorityFee::from_str("-1").is_err());
+    }
+
+    #[test]
+    fn escalates_fees_up_to_the_cap() {
+        let config = PriorityFeeConfig::new(PriorityFee::Fixed(0), Some(5_000), 1.5);
+        assert_eq!(config.escalate(0), 1_500);
+        assert_eq!(config.escalate(1_500), 2_250);
+        assert_eq!(config.escalate(4_000), 5_000);
+        assert_eq!(config.escalate(5_000), 5_000);
+
+        let uncapped = PriorityFeeConfig::new(PriorityFee::Fixed(0), None, 2.0);
+        assert_eq!(uncapped.escalate(100_000), 200_000);
+    }
+
+    #[test]
+    fn picks_percentiles_of_sorted_fees() {
+        let fees = [10, 20, 30, 40, 50];
+        assert_eq!(percentile_of(&fees, 0), 10);
+        assert_eq!(percentile_of(&fees, 50), 30);
+        assert_eq!(percentile_of(&fees, 75), 40);
+        assert_eq!(percentile_of(&fees, 100), 50);
+        assert_eq!(percentile_of(&[], 75), 0);
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use ore::instruction::OreInstruction;
+use serde_json::json;
+use solana_client::{
+    nonblocking::rpc_client::RpcClient, rpc_client::GetConfirmedSignaturesForAddress2Config,
+    rpc_config::RpcTransactionConfig,
+};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{
+    commitment_config::CommitmentConfig, keccak::Hash as KeccakHash, signature::Signature,
+    transaction::VersionedTransaction,
+};
+use solana_transaction_status::UiTransactionEncoding;
+use spl_token::amount_to_ui_amount;
+
+use crate::{
+    error::OreCliError,
+    output::ore_amount,
+    utils::{get_mining_state, parse_address, proof_pubkey},
+    Miner,
+};
+
+// Number of recent proof transactions searched for a successful mine
+const SIGNATURE_LIMIT: usize = 20;
+
+impl Miner {
+    pub async fn proof(&self, address: Option<String>) -> Result<(), OreCliError> {
+        let authority = match address {
+            Some(address) => parse_address(&address)?,
+            None => self.signer()?.pubkey(),
+        };
+        let address = proof_pubkey(authority);
+        let (state, last_advanced_at) = self
+            .rpc
+            .read(|client| async move {
+                let state = get_mining_state(&client, Some(authority)).await?;
+                let last_advanced_at = match state.proof {
+                    Some(_) => last_advanced_at(&client, &address).await?,
+                    None => None,
+                };
+                Ok((state, last_advanced_at))
+            })
+            .await?;
+        let json = json!({
+            "slot": state.slot,
+            "address": address.to_string(),
+            "authority": authority.to_string(),
+            "registered": state.proof.is_some(),
+            "hash": state.proof.map(|proof| KeccakHash::from(proof.hash).to_string()),
+            "claimable_rewards": state.proof.map(|proof| ore_amount(proof.claimable_rewards)),
+            "total_hashes": state.proof.map(|proof| proof.total_hashes),
+            "total_rewards": state.proof.map(|proof| ore_amount(proof.total_rewards)),
+            "last_advanced_at": last_advanced_at,
+        });
+        if self.output.print_json(&json) {
+            return Ok(());
+        }
+
+        println!("Proof: {}", address);
+        println!("Authority: {}", authority);
+        let Some(proof) = state.proof else {
+            println!("Registered: no");
+            return Ok(());
+        };
+        println!("Registered: yes");
+        println!("Hash: {}", KeccakHash::from(proof.hash));
+        println!(
+            "Claimable rewards: {} ORE",
+            amount_to_ui_amount(proof.claimable_rewards, ore::TOKEN_DECIMALS)
+        );
+        println!("Total hashes: {}", proof.total_hashes);
+        println!(
+            "Total rewards: {} ORE",
+            amount_to_ui_amount(proof.total_rewards, ore::TOKEN_DECIMALS)
+        );
+        match last_advanced_at {
+            Some(timestamp) => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |now| now.as_secs() as i64);
+                println!("Last advanced at: {} ({}s ago)", timestamp, now - timestamp);
+            }
+            None => println!("Last advanced at: unknown"),
+        }
+        Ok(())
+    }
+}
+
+/// Returns the block time of the most recent successful transaction that mined with the proof
+/// at `address`. The proof does not store a timestamp of its own, and registering and claiming
+/// write to it without advancing its hash, so only transactions with a `mine` instruction count.
+async fn last_advanced_at(client: &RpcClient, address: &Pubkey) -> Result<Option<i64>, OreCliError> {
+    let config = GetConfirmedSignaturesForAddress2Config {
+        before: None,
+        until: None,
+        limit: Some(SIGNATURE_LIMIT),
+        commitment: Some(client.commitment()),
+    };
+    let signatures = client
+        .get_signatures_for_address_with_config(address, config)
+        .await?;
+    for signature in signatures.into_iter().filter(|signature| signature.err.is_none()) {
+        let Ok(sig) = Signature::from_str(&signature.signature) else {
+            continue;
+        };
+        let config = RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Base64),
+            commitment: Some(CommitmentConfig::confirmed()),
+            max_supported_transaction_version: Some(0),
+        };
+        let tx = client.get_transaction_with_config(&sig, config).await?;
+        if tx.transaction.transaction.decode().is_some_and(|tx| is_mine_transaction(&tx)) {
+            return Ok(tx.block_time.or(signature.block_time));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns whether `tx` includes an Ore `mine` instruction.
+fn is_mine_transaction(tx: &VersionedTransaction) -> bool {
+    let keys = tx.message.static_account_keys();
+    tx.message.instructions().iter().any(|ix| {
+        keys.get(ix.program_id_index as usize) == Some(&ore::ID)
+            && ix.data.first() == Some(&(OreInstruction::Mine as u8))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ore::instruction::OreInstruction;
+    use serde_json::json;
+    use solana_client::nonblocking::rpc_client::RpcClient;
+    use solana_program::{instruction::Instruction, pubkey::Pubkey};
+    use solana_sdk::{
+        commitment_config::CommitmentConfig, signature::Signature, transaction::Transaction,
+    };
+
+    use super::last_advanced_at;
+    use crate::test_utils::mock_server;
+
+    fn encoded_transaction(ix: OreInstruction) -> String {
+        let ix = Instruction {
+            program_id: ore::ID,
+            accounts: vec![],
+            data: vec![ix as u8],
+        };
+        let tx = Transaction::new_with_payer(&[ix], Some(&Pubkey::new_unique()));
+        bs58::encode(bincode::serialize(&tx).unwrap()).into_string()
+    }
+
+    #[tokio::test]
+    async fn skips_transactions_that_do_not_mine() {
+        let url = mock_server(|method, params| match method {
+            "getVersion" => json!({ "solana-core": "1.18.5" }),
+            "getSignaturesForAddress" => json!([
+                {
+                    "signature": Signature::from([1; 64]).to_string(),
+                    "slot": 3,
+                    "err": null,
+                    "memo": null,
+                    "blockTime": 300,
+                },
+                {
+                    "signature": Signature::from([2; 64]).to_string(),
+                    "slot": 2,
+                    "err": { "InstructionError": [0, { "Custom": 1 }] },
+                    "memo": null,
+                    "blockTime": 200,
+                },
+                {
+                    "signature": Signature::from([3; 64]).to_string(),
+                    "slot": 1,
+                    "err": null,
+                    "memo": null,
+                    "blockTime": 100,
+                },
+            ]),
+            "getTransaction" => {
+                let (ix, block_time) = if params[0] == Signature::from([1; 64]).to_string() {
+                    (OreInstruction::Claim, 300)
+                } else if params[0] == Signature::from([3; 64]).to_string() {
+                    (OreInstruction::Mine, 100)
+                } else {
+                    panic!("fetched failed transaction {}", params[0]);
+                };
+                json!({
+                    "slot": 1,
+                    "blockTime": block_time,
+                    "transaction": encoded_transaction(ix),
+                    "meta": null,
+                })
+            }
+            _ => panic!("unexpected method {}", method),
+        })
+        .await;
+        let client = RpcClient::new_with_commitment(url, CommitmentConfig::confirmed());
+        let timestamp = last_advanced_at(&client, &Pubkey::new_unique()).await.unwrap();
+        assert_eq!(timestamp, Some(100));
+    }
+}
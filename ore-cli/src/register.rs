@@ -1,24 +1,36 @@
-use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_sdk::{commitment_config::CommitmentConfig, signature::Signer};
+use std::mem::size_of;
 
-use crate::{utils::proof_pubkey, Miner};
+use ore::state::Proof;
+use solana_sdk::{
+    signature::Signer,
+    system_instruction,
+};
+
+use crate::{error::OreCliError, utils::proof_pubkey, Miner};
 
 impl Miner {
-    pub async fn register(&self) {
+    pub async fn register(&self, signer: &dyn Signer) -> Result<(), OreCliError> {
         // Return early if miner is already registered
-        let signer = self.signer();
         let proof_address = proof_pubkey(signer.pubkey());
-        let client =
-            RpcClient::new_with_commitment(self.cluster.clone(), CommitmentConfig::confirmed());
+        let client = self.rpc_client();
         if client.get_account(&proof_address).await.is_ok() {
-            return;
+            return Ok(());
+        }
+
+        // The program only charges the authority the rent the proof account is missing, so a
+        // separate fee payer funds it in full up front.
+        let mut ixs = vec![];
+        if let Some(payer) = self.fee_payer()? {
+            let rent = client
+                .get_minimum_balance_for_rent_exemption(8 + size_of::<Proof>())
+                .await?;
+            ixs.push(system_instruction::transfer(&payer.pubkey(), &proof_address, rent));
         }
 
         // Sign and send transaction.
         println!("Generating challenge...");
-        let ix = ore::instruction::register(signer.pubkey());
-        self.send_and_confirm(&[ix], true, false)
-            .await
-            .expect("Transaction failed");
+        ixs.push(ore::instruction::register(signer.pubkey()));
+        self.send_and_confirm(signer, &ixs, true, false).await?;
+        Ok(())
     }
 }
@@ -1,24 +1,36 @@
-use std::str::FromStr;
+use serde_json::json;
 
-use solana_program::pubkey::Pubkey;
-use solana_sdk::signature::Signer;
-
-use crate::{utils::get_proof, Miner};
+use crate::{
+    error::OreCliError,
+    output::ore_amount,
+    utils::{get_mining_state, parse_address, proof_pubkey},
+    Miner,
+};
 
 impl Miner {
-    pub async fn rewards(&self, address: Option<String>) {
-        let address = if let Some(address) = address {
-            if let Ok(address) = Pubkey::from_str(&address) {
-                address
-            } else {
-                println!("Invalid address: {:?}", address);
-                return;
-            }
-        } else {
-            self.signer().pubkey()
+    pub async fn rewards(&self, address: Option<String>) -> Result<(), OreCliError> {
+        let address = match address {
+            Some(address) => parse_address(&address)?,
+            None => self.signer()?.pubkey(),
         };
-        let proof = get_proof(self.cluster.clone(), address).await;
+        let state = self
+            .rpc
+            .read(|client| async move { get_mining_state(&client, Some(address)).await })
+            .await?;
+        let proof = state
+            .proof
+            .ok_or_else(|| OreCliError::AccountNotFound(format!("proof of {}", address)))?;
+        let json = json!({
+            "slot": state.slot,
+            "authority": address.to_string(),
+            "proof": proof_pubkey(address).to_string(),
+            "claimable_rewards": ore_amount(proof.claimable_rewards),
+        });
+        if self.output.print_json(&json) {
+            return Ok(());
+        }
         let amount = (proof.claimable_rewards as f64) / 10f64.powf(ore::TOKEN_DECIMALS as f64);
         println!("{:} ORE", amount);
+        Ok(())
     }
 }
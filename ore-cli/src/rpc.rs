@@ -0,0 +1,357 @@
+use std::{
+    fmt,
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use futures::{future::join_all, stream::FuturesUnordered, StreamExt};
+use rand::Rng;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind, Result as ClientResult},
+    nonblocking::rpc_client::RpcClient,
+    rpc_config::RpcSendTransactionConfig,
+};
+use solana_rpc_client::{http_sender::HttpSender, rpc_client::RpcClientConfig};
+use solana_sdk::{commitment_config::CommitmentConfig, signature::Signature, transaction::Transaction};
+
+use crate::{error::OreCliError, utils::config_file_list};
+
+// Interval between endpoint health checks
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+// Time to wait for a health check response before counting it as an error
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Number of slots an endpoint may trail the most recent one before it is considered unhealthy
+const MAX_SLOT_LAG: u64 = 20;
+
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Connection settings shared by the clients of every endpoint.
+#[derive(Clone, Copy, Debug)]
+pub struct RpcSettings {
+    /// Commitment level for reads and confirmations.
+    pub commitment: CommitmentConfig,
+
+    /// Time to wait for a response before a request fails.
+    pub timeout: Duration,
+
+    /// Time to keep idle HTTP connections open for reuse.
+    pub keep_alive: Duration,
+}
+
+/// The RPC endpoints the miner reads from and broadcasts transactions to.
+pub struct RpcEndpoints {
+    urls: Vec<String>,
+    clients: Vec<Arc<RpcClient>>,
+    primary: AtomicUsize,
+    health: Mutex<Vec<EndpointHealth>>,
+}
+
+/// What the miner has observed about a single endpoint.
+#[derive(Clone, Debug, Default)]
+pub struct EndpointHealth {
+    /// Round trip time of the last successful health check.
+    pub latency: Option<Duration>,
+
+    /// Slot reported by the last successful health check.
+    pub slot: Option<u64>,
+
+    /// Number of requests that have failed since the last successful health check.
+    pub errors: u32,
+}
+
+impl RpcEndpoints {
+    pub fn new(urls: Vec<String>, settings: RpcSettings) -> Self {
+        assert!(!urls.is_empty(), "At least one RPC endpoint is required");
+        let clients = urls
+            .iter()
+            .map(|url| Arc::new(build_client(url, settings)))
+            .collect();
+        let health = vec![EndpointHealth::default(); urls.len()];
+        Self {
+            urls,
+            clients,
+            primary: AtomicUsize::new(0),
+            health: Mutex::new(health),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.urls.len()
+    }
+
+    /// Returns the client of the endpoint reads are currently sent to.
+    pub fn client(&self) -> Arc<RpcClient> {
+        self.clients[self.primary.load(Ordering::Relaxed)].clone()
+    }
+
+    /// Runs the read `f` against the primary endpoint, failing over to the next endpoint each
+    /// time it fails with an RPC error, until every endpoint has been tried.
+    pub async fn read<T, F, Fut>(&self, f: F) -> Result<T, OreCliError>
+    where
+        F: Fn(Arc<RpcClient>) -> Fut,
+        Fut: Future<Output = Result<T, OreCliError>>,
+    {
+        let mut last_err = None;
+        for _ in 0..self.urls.len() {
+            let client = self.client();
+            match f(client.clone()).await {
+                Err(err @ OreCliError::Rpc(_)) => {
+                    self.record_error(&client.url());
+                    last_err = Some(err);
+                }
+                result => return result,
+            }
+        }
+        Err(last_err.expect("no endpoints to read from"))
+    }
+
+    /// Records a failed request to `url` and moves reads to the next healthy endpoint if `url`
+    /// is the primary one.
+    pub fn record_error(&self, url: &str) {
+        let mut health = self.health.lock().expect("failed to lock mutex");
+        let Some(i) = self.urls.iter().position(|u| u == url) else {
+            return;
+        };
+        health[i].errors += 1;
+        if self.primary.load(Ordering::Relaxed) != i || self.urls.len() < 2 {
+            return;
+        }
+        let max_slot = max_slot(&health);
+        let next = (1..self.urls.len())
+            .map(|offset| (i + offset) % self.urls.len())
+            .find(|j| is_healthy(&health[*j], max_slot))
+            .unwrap_or((i + 1) % self.urls.len());
+        self.primary.store(next, Ordering::Relaxed);
+        println!("RPC {} failed, switching to {}", url, self.urls[next]);
+    }
+
+    /// Measures the latency and slot of every endpoint, and moves reads to the first healthy
+    /// endpoint in the configured order.
+    pub async fn check_health(&self) {
+        let checks = self.clients.iter().map(|client| async move {
+            let started_at = Instant::now();
+            match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, client.get_slot()).await {
+                Ok(Ok(slot)) => Some((slot, started_at.elapsed())),
+                _ => None,
+            }
+        });
+        let results = join_all(checks).await;
+
+        let mut health = self.health.lock().expect("failed to lock mutex");
+        for (health, result) in health.iter_mut().zip(results) {
+            match result {
+                Some((slot, latency)) => {
+                    *health = EndpointHealth {
+                        latency: Some(latency),
+                        slot: Some(slot),
+                        errors: 0,
+                    };
+                }
+                None => health.errors += 1,
+            }
+        }
+        let max_slot = max_slot(&health);
+        let primary = self.primary.load(Ordering::Relaxed);
+        if let Some(best) = health.iter().position(|h| is_healthy(h, max_slot)) {
+            if best != primary {
+                println!("Switching RPC from {} to {}", self.urls[primary], self.urls[best]);
+                self.primary.store(best, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Checks the health of every endpoint at a fixed interval, forever.
+    pub async fn monitor(self: Arc<Self>) {
+        if self.urls.len() < 2 {
+            return;
+        }
+        loop {
+            self.check_health().await;
+            tokio::time::sleep(HEALTH_CHECK_INTERVAL).await;
+        }
+    }
+
+    /// Sends `tx` to every endpoint concurrently and returns its signature as soon as one
+    /// accepts it. Sends to the remaining endpoints continue in the background.
+    pub async fn broadcast(
+        &self,
+        tx: &Transaction,
+        config: RpcSendTransactionConfig,
+    ) -> ClientResult<Signature> {
+        let mut sends: FuturesUnordered<_> = self
+            .clients
+            .iter()
+            .map(|client| {
+                let client = client.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let res = client.send_transaction_with_config(&tx, config).await;
+                    (client.url(), res)
+                })
+            })
+            .collect();
+        let mut last_err = None;
+        while let Some(joined) = sends.next().await {
+            match joined {
+                Ok((url, Ok(sig))) => {
+                    println!("Transaction first accepted by {}", url);
+                    return Ok(sig);
+                }
+                Ok((url, Err(err))) => {
+                    println!("Error sending transaction to {}: {}", url, err);
+                    last_err = Some(err);
+                }
+                Err(err) => {
+                    last_err = Some(ClientError {
+                        request: None,
+                        kind: ClientErrorKind::Custom(format!("Send task failed: {}", err)),
+                    });
+                }
+            }
+        }
+        Err(last_err.expect("no endpoints to send to"))
+    }
+}
+
+impl fmt::Display for RpcEndpoints {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let health = self.health.lock().expect("failed to lock mutex");
+        let max_slot = max_slot(&health);
+        let primary = self.primary.load(Ordering::Relaxed);
+        for (i, (url, health)) in self.urls.iter().zip(health.iter()).enumerate() {
+            let marker = if i == primary { '*' } else { ' ' };
+            write!(f, "{} {}", marker, url)?;
+            if let Some(latency) = health.latency {
+                write!(f, " {}ms", latency.as_millis())?;
+            }
+            if let (Some(max_slot), Some(slot)) = (max_slot, health.slot) {
+                write!(f, ", {} slots behind", max_slot - slot)?;
+            }
+            writeln!(f, ", {} errors", health.errors)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns an RPC client for `url` that reuses its HTTP connections.
+fn build_client(url: &str, settings: RpcSettings) -> RpcClient {
+    let http = reqwest::Client::builder()
+        .default_headers(HttpSender::default_headers())
+        .timeout(settings.timeout)
+        .pool_idle_timeout(settings.keep_alive)
+        .tcp_keepalive(settings.keep_alive)
+        .build()
+        .expect("Failed to build http client");
+    RpcClient::new_sender(
+        HttpSender::new_with_client(url, http),
+        RpcClientConfig::with_commitment(settings.commitment),
+    )
+}
+
+/// Returns how long to wait before retrying after `attempt` consecutive failures: an
+/// exponentially growing delay with up to 50% random jitter.
+pub fn backoff_delay(attempt: u32) -> Duration {
+    let delay = BASE_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(MAX_BACKOFF);
+    let jitter = rand::thread_rng().gen_range(0.0..=0.5);
+    delay.mul_f64(1. + jitter)
+}
+
+fn max_slot(health: &[EndpointHealth]) -> Option<u64> {
+    health.iter().filter_map(|h| h.slot).max()
+}
+
+fn is_healthy(health: &EndpointHealth, max_slot: Option<u64>) -> bool {
+    let lag = match (max_slot, health.slot) {
+        (Some(max_slot), Some(slot)) => max_slot - slot,
+        _ => 0,
+    };
+    health.errors == 0 && lag <= MAX_SLOT_LAG
+}
+
+/// Returns the extra RPC addresses listed under `json_rpc_urls` in a Solana CLI config file.
+pub fn config_file_urls(config_file: &str) -> Vec<String> {
+    config_file_list(config_file, "json_rpc_urls")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+    use solana_sdk::commitment_config::CommitmentConfig;
+    use tokio::net::TcpListener;
+
+    use super::{backoff_delay, config_file_urls, RpcEndpoints, RpcSettings, MAX_BACKOFF};
+    use crate::{error::OreCliError, test_utils::mock_server};
+
+    #[test]
+    fn errors_fail_over_only_from_the_primary() {
+        let settings = RpcSettings {
+            commitment: CommitmentConfig::confirmed(),
+            timeout: Duration::from_secs(1),
+            keep_alive: Duration::from_secs(1),
+        };
+        let rpc = RpcEndpoints::new(vec!["http://a".into(), "http://b".into()], settings);
+        rpc.record_error("http://b");
+        assert_eq!(rpc.client().url(), "http://a");
+        rpc.record_error("http://a");
+        assert_eq!(rpc.client().url(), "http://b");
+        rpc.record_error("http://b");
+        assert_eq!(rpc.client().url(), "http://a");
+    }
+
+    #[tokio::test]
+    async fn reads_fail_over_to_the_next_endpoint() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let dead_url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let live_url = mock_server(|method, _| match method {
+            "getVersion" => json!({ "solana-core": "1.18.5" }),
+            "getSlot" => json!(42),
+            _ => panic!("unexpected method {}", method),
+        })
+        .await;
+        let settings = RpcSettings {
+            commitment: CommitmentConfig::confirmed(),
+            timeout: Duration::from_secs(1),
+            keep_alive: Duration::from_secs(1),
+        };
+        let rpc = RpcEndpoints::new(vec![dead_url, live_url.clone()], settings);
+        let slot = rpc
+            .read(|client| async move { client.get_slot().await.map_err(OreCliError::from) })
+            .await
+            .unwrap();
+        assert_eq!(slot, 42);
+        assert_eq!(rpc.client().url(), live_url);
+    }
+
+    #[test]
+    fn backoff_grows_and_is_capped() {
+        assert!(backoff_delay(1) >= Duration::from_millis(500));
+        assert!(backoff_delay(1) <= Duration::from_millis(750));
+        assert!(backoff_delay(3) >= Duration::from_secs(2));
+        assert!(backoff_delay(100) <= MAX_BACKOFF.mul_f64(1.5));
+    }
+
+    #[test]
+    fn reads_extra_urls_from_config_file() {
+        let path = std::env::temp_dir().join(format!("ore-cli-rpc-{}.yml", std::process::id()));
+        std::fs::write(
+            &path,
+            "json_rpc_url: http://a\njson_rpc_urls:\n  - http://b\n  - http://c\n",
+        )
+        .unwrap();
+        let urls = config_file_urls(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(urls, vec!["http://b", "http://c"]);
+    }
+}
@@ -1,83 +1,209 @@
-use std::{
-    io::{stdout, Write},
-    time::Duration,
-};
+use std::time::Duration;
 
+use futures::StreamExt;
 use solana_client::{
     client_error::{ClientError, ClientErrorKind, Result as ClientResult},
-    nonblocking::rpc_client::RpcClient,
-    rpc_config::{RpcSendTransactionConfig, RpcSimulateTransactionConfig},
+    nonblocking::{
+        pubsub_client::{PubsubClient, PubsubClientError, PubsubClientResult},
+        rpc_client::RpcClient,
+    },
+    rpc_config::{RpcSendTransactionConfig, RpcSignatureSubscribeConfig, RpcSimulateTransactionConfig},
+    rpc_response::RpcSignatureResult,
 };
 use solana_program::instruction::Instruction;
 use solana_sdk::{
-    commitment_config::{CommitmentConfig, CommitmentLevel},
     compute_budget::ComputeBudgetInstruction,
     signature::{Signature, Signer},
-    transaction::Transaction,
+    transaction::{Transaction, TransactionError},
 };
-use solana_transaction_status::{TransactionConfirmationStatus, UiTransactionEncoding};
+use solana_transaction_status::UiTransactionEncoding;
 
-use crate::Miner;
+use crate::{error::OreCliError, rpc::RpcEndpoints, signer::transaction_signers, Miner};
 
 const RPC_RETRIES: usize = 0;
 const SIMULATION_RETRIES: usize = 4;
 const GATEWAY_RETRIES: usize = 4;
-const CONFIRM_RETRIES: usize = 4;
+const CONFIRM_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The final state of a sent transaction.
+#[derive(Debug)]
+pub enum ConfirmationOutcome {
+    /// The transaction was confirmed without error.
+    Landed,
+
+    /// The transaction's blockhash expired before it was confirmed. It can never land.
+    Expired,
+
+    /// The transaction landed but failed.
+    Failed(TransactionError),
+}
 
 impl Miner {
     pub async fn send_and_confirm(
         &self,
+        signer: &dyn Signer,
         ixs: &[Instruction],
         dynamic_cus: bool,
         skip_confirm: bool,
-    ) -> ClientResult<Signature> {
-        let mut stdout = stdout();
-        let signer = self.signer();
-        let client = RpcClient::new_with_commitment(self.cluster.clone(), CommitmentConfig::confirmed());
+    ) -> Result<Signature, OreCliError> {
+        let payer = self.fee_payer()?.map_or(signer.pubkey(), |payer| payer.pubkey());
+        let tip_ix = self.tip.tip_ix(&payer);
+        self.send_and_confirm_tipped(signer, ixs, tip_ix, dynamic_cus, skip_confirm)
+            .await
+    }
+
+    /// Sends `ixs` followed by `tip_ix`, a tip already picked for this submission. The tip is
+    /// counted against the hourly limit once the transaction lands.
+    pub async fn send_and_confirm_tipped(
+        &self,
+        signer: &dyn Signer,
+        ixs: &[Instruction],
+        tip_ix: Option<Instruction>,
+        dynamic_cus: bool,
+        skip_confirm: bool,
+    ) -> Result<Signature, OreCliError> {
+        let fee_payer = self.fee_payer()?;
+        let payer: &dyn Signer = match &fee_payer {
+            Some(fee_payer) => fee_payer.as_ref(),
+            None => signer,
+        };
 
-        // Check the signer's balance before attempting to send the transaction
-        let balance = client
-            .get_balance_with_commitment(&signer.pubkey(), CommitmentConfig::confirmed())
+        // Check the payer's balance before attempting to send the transaction
+        let payer_pubkey = payer.pubkey();
+        let balance = self
+            .rpc
+            .read(|client| async move {
+                Ok(client
+                    .get_balance_with_commitment(&payer_pubkey, client.commitment())
+                    .await?
+                    .value)
+            })
             .await?;
-        if balance.value <= 0 {
-            return Err(ClientError {
-                request: None,
-                kind: ClientErrorKind::Custom("Insufficient SOL balance".into()),
-            });
+        if balance == 0 {
+            return Err(OreCliError::InsufficientFunds);
         }
 
-        // Prepare the transaction
-        let (mut hash, mut slot) = client
-            .get_latest_blockhash_with_commitment(CommitmentConfig::confirmed())
-            .await?;
-        let mut send_cfg = RpcSendTransactionConfig {
+        // Price compute units and append the configured tip, if any
+        let mut fee = self.get_priority_fee(ixs).await;
+        let mut ixs = ixs.to_vec();
+        let tipped = tip_ix.is_some();
+        ixs.extend(tip_ix);
+
+        // Optionally simulate the transaction and prepend a compute unit limit
+        if dynamic_cus {
+            let client = self.rpc_client();
+            let sim_ixs = priced_ixs(&ixs, fee);
+            let tx = Transaction::new_with_payer(&sim_ixs, Some(&payer.pubkey()));
+            if let Some(units_consumed) = simulate_transaction(&client, &tx, &sim_ixs).await? {
+                let cu_budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(units_consumed as u32 + 1000);
+                ixs.insert(0, cu_budget_ix);
+            }
+        }
+
+        // Submit the transaction, re-signing with a fresh blockhash and a higher fee on each retry
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            println!("Attempt {}: paying {} microlamports per compute unit", attempts, fee);
+            let tx_ixs = priced_ixs(&ixs, fee);
+            match self.sign_and_submit(payer, signer, &tx_ixs, skip_confirm).await {
+                Ok(sig) => {
+                    if tipped {
+                        self.tip.record_tip();
+                    }
+                    return Ok(sig);
+                }
+                // The transaction executed, so resending it would fail the same way
+                Err(ClientError {
+                    kind: ClientErrorKind::TransactionError(err),
+                    ..
+                }) => return Err(OreCliError::from_transaction_error(err, &tx_ixs)),
+                // A signer that refused or failed once will not sign the retry either
+                Err(ClientError {
+                    kind: ClientErrorKind::SigningError(err),
+                    ..
+                }) => return Err(OreCliError::Keypair(err.to_string())),
+                Err(err) => println!("Attempt {} failed: {}", attempts, err),
+            }
+            if attempts >= GATEWAY_RETRIES {
+                return Err(OreCliError::Rpc(ClientError {
+                    request: None,
+                    kind: ClientErrorKind::Custom("Exceeded maximum retries for sending transaction".into()),
+                }));
+            }
+            fee = self.priority_fee.escalate(fee);
+            tokio::time::sleep(Duration::from_secs(2)).await;
+        }
+    }
+
+    /// Signs `ixs` with a fresh blockhash, paid for by `payer`, broadcasts the transaction to
+    /// every RPC endpoint, and optionally waits for it to land.
+    async fn sign_and_submit(
+        &self,
+        payer: &dyn Signer,
+        signer: &dyn Signer,
+        ixs: &[Instruction],
+        skip_confirm: bool,
+    ) -> ClientResult<Signature> {
+        let client = self.rpc_client();
+        let (hash, last_valid_block_height) = client
+            .get_latest_blockhash_with_commitment(client.commitment())
+            .await
+            .map_err(|err| {
+                self.rpc.record_error(&client.url());
+                err
+            })?;
+        let send_cfg = RpcSendTransactionConfig {
             skip_preflight: true,
-            preflight_commitment: Some(CommitmentLevel::Confirmed),
+            preflight_commitment: Some(client.commitment().commitment),
             encoding: Some(UiTransactionEncoding::Base64),
             max_retries: Some(RPC_RETRIES),
-            min_context_slot: Some(slot),
+            min_context_slot: None,
         };
-        let mut tx = Transaction::new_with_payer(ixs, Some(&signer.pubkey()));
-
-        // Optionally simulate the transaction
-        if dynamic_cus {
-            simulate_transaction(&client, &mut tx, &mut sim_attempts).await?;
+        let mut tx = Transaction::new_with_payer(ixs, Some(&payer.pubkey()));
+        tx.try_sign(&transaction_signers(payer, signer), hash)?;
+        let sig = self.rpc.broadcast(&tx, send_cfg).await?;
+        println!("Transaction sent with signature: {:?}", sig);
+        if skip_confirm {
+            return Ok(sig);
+        }
+        match confirm_transaction(&self.rpc, self.websocket_url.as_deref(), &sig, last_valid_block_height).await {
+            ConfirmationOutcome::Landed => {
+                println!("Transaction confirmed!");
+                Ok(sig)
+            }
+            ConfirmationOutcome::Expired => Err(ClientError {
+                request: None,
+                kind: ClientErrorKind::Custom(format!("Transaction {} expired before landing", sig)),
+            }),
+            ConfirmationOutcome::Failed(err) => Err(err.into()),
         }
-
-        // Submit the transaction and handle retries
-        submit_transaction(&client, &mut tx, &mut send_cfg, &mut sigs, &mut attempts, skip_confirm).await
     }
 }
 
-async fn simulate_transaction(client: &RpcClient, tx: &mut Transaction, sim_attempts: &mut usize) -> ClientResult<()> {
-    while *sim_attempts < SIMULATION_RETRIES {
+/// Returns `ixs` with a set_compute_unit_price instruction for `fee` prepended.
+pub fn priced_ixs(ixs: &[Instruction], fee: u64) -> Vec<Instruction> {
+    let mut priced_ixs = vec![ComputeBudgetInstruction::set_compute_unit_price(fee)];
+    priced_ixs.extend_from_slice(ixs);
+    priced_ixs
+}
+
+/// Simulates `tx`, made of `ixs`, and returns the compute units it consumed. Ore program errors
+/// are returned immediately since resimulating would fail the same way.
+async fn simulate_transaction(
+    client: &RpcClient,
+    tx: &Transaction,
+    ixs: &[Instruction],
+) -> Result<Option<u64>, OreCliError> {
+    let mut sim_attempts = 0;
+    while sim_attempts < SIMULATION_RETRIES {
         let sim_res = client
             .simulate_transaction_with_config(
                 tx,
                 RpcSimulateTransactionConfig {
                     sig_verify: false,
                     replace_recent_blockhash: true,
-                    commitment: Some(CommitmentConfig::confirmed()),
+                    commitment: Some(client.commitment()),
                     encoding: Some(UiTransactionEncoding::Base64),
                     accounts: None,
                     min_context_slot: None,
@@ -87,127 +213,213 @@ async fn simulate_transaction(client: &RpcClient, tx: &mut Transaction, sim_atte
             .await;
 
         match sim_res {
-            Ok(sim_res) if sim_res.value.err.is_none() => {
-                if let Some(units_consumed) = sim_res.value.units_consumed {
-                    let cu_budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(units_consumed as u32 + 1000);
-                    tx.message.instructions.insert(0, cu_budget_ix);
-                    return Ok(());
+            Ok(sim_res) => match sim_res.value.err {
+                None => return Ok(sim_res.value.units_consumed),
+                Some(err) => {
+                    let err = OreCliError::from_transaction_error(err, ixs);
+                    println!("Simulation error: {}", err);
+                    if matches!(err, OreCliError::Ore(_)) {
+                        return Err(err);
+                    }
+                    sim_attempts += 1;
                 }
             },
-            Ok(sim_res) => {
-                println!("Simulation error: {:?}", sim_res.value.err);
-                *sim_attempts += 1;
-            },
             Err(e) => {
                 println!("Simulation error: {:?}", e);
-                *sim_attempts += 1;
-                if *sim_attempts >= SIMULATION_RETRIES {
-                    return Err(ClientError {
+                sim_attempts += 1;
+                if sim_attempts >= SIMULATION_RETRIES {
+                    return Err(OreCliError::Rpc(ClientError {
                         request: None,
                         kind: ClientErrorKind::Custom("Simulation repeatedly failed".into()),
-                    });
+                    }));
                 }
             }
         }
     }
-    Ok(())
+    Ok(None)
 }
 
-async fn simulate_transaction(client: &RpcClient, tx: &mut Transaction, sim_attempts: &mut usize) -> ClientResult<()> {
-    while *sim_attempts < SIMULATION_RETRIES {
-        let sim_res = client
-            .simulate_transaction_with_config(
-                tx,
-                RpcSimulateTransactionConfig {
-                    sig_verify: false,
-                    replace_recent_blockhash: true,
-                    commitment: Some(CommitmentConfig::confirmed()),
-                    encoding: Some(UiTransactionEncoding::Base64),
-                    accounts: None,
-                    min_context_slot: None,
-                    inner_instructions: false,
-                },
-            )
-            .await;
+/// Waits for `signature` to land, fail, or expire, through a websocket subscription if
+/// `websocket_url` is set and by polling otherwise.
+async fn confirm_transaction(
+    rpc: &RpcEndpoints,
+    websocket_url: Option<&str>,
+    signature: &Signature,
+    last_valid_block_height: u64,
+) -> ConfirmationOutcome {
+    if let Some(websocket_url) = websocket_url {
+        match subscribe_confirmation(rpc, websocket_url, signature, last_valid_block_height).await {
+            Ok(outcome) => return outcome,
+            Err(err) => println!("Websocket confirmation failed ({}), falling back to polling", err),
+        }
+    }
+    poll_confirmation(rpc, signature, last_valid_block_height).await
+}
 
-        match sim_res {
-            Ok(sim_res) if sim_res.value.err.is_none() => {
-                if let Some(units_consumed) = sim_res.value.units_consumed {
-                    let cu_budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(units_consumed as u32 + 1000);
-                    tx.message.instructions.insert(0, cu_budget_ix);
-                    return Ok(());
+/// Waits for a signatureSubscribe notification for `signature`, checking the block height
+/// between notifications to detect expiry.
+async fn subscribe_confirmation(
+    rpc: &RpcEndpoints,
+    websocket_url: &str,
+    signature: &Signature,
+    last_valid_block_height: u64,
+) -> PubsubClientResult<ConfirmationOutcome> {
+    let pubsub = PubsubClient::new(websocket_url).await?;
+    let config = RpcSignatureSubscribeConfig {
+        commitment: Some(rpc.client().commitment()),
+        enable_received_notification: Some(false),
+    };
+    let (mut notifications, unsubscribe) = pubsub.signature_subscribe(signature, Some(config)).await?;
+
+    // The transaction may have landed before the subscription was opened
+    let mut outcome = check_status_with_failover(rpc, signature, last_valid_block_height).await;
+    while outcome.is_none() {
+        tokio::select! {
+            notification = notifications.next() => match notification {
+                Some(notification) => {
+                    if let RpcSignatureResult::ProcessedSignature(result) = notification.value {
+                        outcome = Some(match result.err {
+                            Some(err) => ConfirmationOutcome::Failed(err),
+                            None => ConfirmationOutcome::Landed,
+                        });
+                    }
+                }
+                None => {
+                    return Err(PubsubClientError::ConnectionClosed(
+                        "signature subscription ended".into(),
+                    ))
                 }
             },
-            Ok(sim_res) => {
-                println!("Simulation error: {:?}", sim_res.value.err);
-                *sim_attempts += 1;
-            },
-            Err(e) => {
-                println!("Simulation error: {:?}", e);
-                *sim_attempts += 1;
-                if *sim_attempts >= SIMULATION_RETRIES {
-                    return Err(ClientError {
-                        request: None,
-                        kind: ClientErrorKind::Custom("Simulation repeatedly failed".into()),
-                    });
+            _ = tokio::time::sleep(CONFIRM_INTERVAL) => {
+                let client = rpc.client();
+                match client.get_block_height().await {
+                    Ok(block_height) if block_height > last_valid_block_height => {
+                        outcome = check_status_with_failover(rpc, signature, last_valid_block_height).await;
+                    }
+                    Ok(_) => {}
+                    Err(_) => rpc.record_error(&client.url()),
                 }
             }
         }
     }
-    Ok(())
+    drop(notifications);
+    unsubscribe().await;
+    pubsub.shutdown().await.ok();
+    Ok(outcome.unwrap())
 }
 
-async fn submit_transaction(client: &RpcClient, tx: &Transaction, send_cfg: &RpcSendTransactionConfig, skip_confirm: bool) -> ClientResult<Signature> {
-    let mut attempts = 0;
-    while attempts < GATEWAY_RETRIES {
-        let response = client.send_transaction_with_config(tx, send_cfg.clone()).await;
-        match response {
-            Ok(sig) => {
-                println!("Transaction sent with signature: {:?}", sig);
-                if skip_confirm {
-                    return Ok(sig);
-                } else {
-                    return confirm_transaction(client, &sig).await;
-                }
-            },
-            Err(e) => {
-                println!("Error sending transaction: {:?}", e);
-                attempts += 1;
-                thread::sleep(Duration::from_secs(2));
-                continue;
-            }
+/// Polls the status of `signature` until it lands, fails, or its blockhash expires.
+async fn poll_confirmation(rpc: &RpcEndpoints, signature: &Signature, last_valid_block_height: u64) -> ConfirmationOutcome {
+    loop {
+        tokio::time::sleep(CONFIRM_INTERVAL).await;
+        if let Some(outcome) = check_status_with_failover(rpc, signature, last_valid_block_height).await {
+            return outcome;
         }
     }
-    Err(ClientError {
-        request: None,
-        kind: ClientErrorKind::Custom("Exceeded maximum retries for sending transaction".into()),
-    })
 }
 
-async fn confirm_transaction(client: &RpcClient, signature: &Signature) -> ClientResult<Signature> {
-    let mut attempts = 0;
-    while attempts < CONFIRM_RETRIES {
-        thread::sleep(Duration::from_secs(2));
-        let status = client.get_signature_statuses(&[signature.clone()]).await?;
-        if let Some(status) = status.value.first().flatten() {
-            match status.confirmation_status {
-                Some(TransactionConfirmationStatus::Confirmed) |
-                Some(TransactionConfirmationStatus::Finalized) => {
-                    println!("Transaction confirmed!");
-                    return Ok(*signature);
-                },
-                _ => {
-                    attempts += 1;
-                    continue;
-                }
-            }
-        } else {
-            println!("Transaction status not available");
-            attempts += 1;
+/// Checks the status of `signature` once on the primary endpoint, recording a failed request so
+/// the next check goes to another endpoint. Returns `None` while the transaction can still land.
+async fn check_status_with_failover(
+    rpc: &RpcEndpoints,
+    signature: &Signature,
+    last_valid_block_height: u64,
+) -> Option<ConfirmationOutcome> {
+    let client = rpc.client();
+    match check_status(&client, signature, last_valid_block_height).await {
+        Ok(outcome) => outcome,
+        Err(err) => {
+            println!("Error fetching transaction status from {}: {}", client.url(), err);
+            rpc.record_error(&client.url());
+            None
+        }
+    }
+}
+
+/// Checks the status of `signature` once, returning `None` while it can still land.
+async fn check_status(
+    client: &RpcClient,
+    signature: &Signature,
+    last_valid_block_height: u64,
+) -> ClientResult<Option<ConfirmationOutcome>> {
+    // Read the block height first so a transaction that lands in between is still seen below
+    let block_height = client.get_block_height().await?;
+    let statuses = client.get_signature_statuses(&[*signature]).await?;
+    if let Some(status) = statuses.value.first().and_then(|s| s.as_ref()) {
+        if let Some(err) = &status.err {
+            return Ok(Some(ConfirmationOutcome::Failed(err.clone())));
+        }
+        if status.satisfies_commitment(client.commitment()) {
+            return Ok(Some(ConfirmationOutcome::Landed));
         }
+        // The transaction landed but hasn't reached the commitment yet, so resending it could
+        // land it twice however far past its blockhash the chain is
+        return Ok(None);
     }
-    Err(ClientError {
-        request: None,
-        kind: ClientErrorKind::Custom("Transaction confirmation failed after repeated attempts".into()),
-    })
-}
\ No newline at end of file
+    Ok((block_height > last_valid_block_height).then_some(ConfirmationOutcome::Expired))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use solana_client::nonblocking::rpc_client::RpcClient;
+    use solana_sdk::{commitment_config::CommitmentConfig, signature::Signature};
+
+    use super::{check_status, ConfirmationOutcome};
+    use crate::test_utils::mock_server;
+
+    fn unlanded_status(method: &str, block_height: u64) -> Value {
+        match method {
+            "getVersion" => json!({ "solana-core": "1.18.5" }),
+            "getBlockHeight" => json!(block_height),
+            "getSignatureStatuses" => json!({ "context": { "slot": 1 }, "value": [null] }),
+            _ => panic!("unexpected method {}", method),
+        }
+    }
+
+    #[tokio::test]
+    async fn unlanded_transaction_expires_only_after_its_blockhash() {
+        let url = mock_server(|method, _| unlanded_status(method, 100)).await;
+        let client = RpcClient::new(url);
+        let signature = Signature::new_unique();
+        assert!(check_status(&client, &signature, 150).await.unwrap().is_none());
+
+        let url = mock_server(|method, _| unlanded_status(method, 200)).await;
+        let client = RpcClient::new(url);
+        assert!(matches!(
+            check_status(&client, &signature, 150).await.unwrap(),
+            Some(ConfirmationOutcome::Expired)
+        ));
+    }
+    fn processed_status(method: &str) -> Value {
+        match method {
+            "getVersion" => json!({ "solana-core": "1.18.5" }),
+            "getBlockHeight" => json!(200),
+            "getSignatureStatuses" => json!({
+                "context": { "slot": 1 },
+                "value": [{
+                    "slot": 1,
+                    "confirmations": 0,
+                    "err": null,
+                    "status": { "Ok": null },
+                    "confirmationStatus": "processed",
+                }],
+            }),
+            _ => panic!("unexpected method {}", method),
+        }
+    }
+
+    #[tokio::test]
+    async fn processed_transaction_past_its_blockhash_is_still_awaited() {
+        let url = mock_server(|method, _| processed_status(method)).await;
+        let signature = Signature::new_unique();
+        let client = RpcClient::new_with_commitment(url.clone(), CommitmentConfig::confirmed());
+        assert!(check_status(&client, &signature, 150).await.unwrap().is_none());
+
+        let client = RpcClient::new_with_commitment(url, CommitmentConfig::processed());
+        assert!(matches!(
+            check_status(&client, &signature, 150).await.unwrap(),
+            Some(ConfirmationOutcome::Landed)
+        ));
+    }
+}
@@ -0,0 +1,284 @@
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use bip39::{Language, Mnemonic, Seed};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{
+    derivation_path::DerivationPath,
+    signature::{keypair_from_seed, keypair_from_seed_and_derivation_path, Keypair, Signer},
+};
+
+#[cfg(unix)]
+use crate::agent::AgentSigner;
+use crate::{error::OreCliError, keys, utils::read_keypair, Miner};
+
+/// A signer shared by every task of the miner.
+pub type SharedSigner = Arc<dyn Signer + Send + Sync>;
+
+/// Where to load a signer from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SignerSource {
+    /// A JSON keypair file, given as `PATH` or `file:PATH`.
+    File(String),
+
+    /// A base58 or JSON array secret key in an environment variable, given as `env:NAME`.
+    Env(String),
+
+    /// A BIP39 seed phrase in an environment variable, given as `seed:NAME[?key=ACCOUNT/CHANGE]`.
+    SeedPhrase {
+        var: String,
+        derivation_path: Option<DerivationPath>,
+    },
+
+    /// A seed phrase or secret key typed at a prompt, given as `prompt:[?key=ACCOUNT/CHANGE]`.
+    Prompt {
+        derivation_path: Option<DerivationPath>,
+    },
+
+    /// A key held by an `ore agent` listening on a Unix socket, given as
+    /// `agent:SOCKET[?pubkey=PUBKEY]`.
+    Agent {
+        socket: String,
+        pubkey: Option<Pubkey>,
+    },
+
+    /// A key in the encrypted keystore, given as `keystore:NAME` for a key in the default
+    /// keystore or `keystore:PATH` for a key file elsewhere.
+    Keystore(String),
+}
+
+impl FromStr for SignerSource {
+    type Err = OreCliError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            |msg: &str| OreCliError::InvalidInput(format!("invalid signer `{}`: {}", s, msg));
+        let Some((scheme, rest)) = s.split_once(':') else {
+            return Ok(SignerSource::File(s.to_string()));
+        };
+        let (value, query) = match rest.split_once('?') {
+            Some((value, query)) => (value, Some(query)),
+            None => (rest, None),
+        };
+        let param = |name: &str| {
+            query.and_then(|query| {
+                query
+                    .split('&')
+                    .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+            })
+        };
+        let derivation_path = param("key")
+            .map(|key| DerivationPath::from_key_str(key).map_err(|err| invalid(&err.to_string())))
+            .transpose()?;
+        match scheme {
+            "file" => Ok(SignerSource::File(rest.to_string())),
+            "env" => Ok(SignerSource::Env(rest.to_string())),
+            "keystore" => Ok(SignerSource::Keystore(rest.to_string())),
+            "seed" => Ok(SignerSource::SeedPhrase {
+                var: value.to_string(),
+                derivation_path,
+            }),
+            "prompt" => Ok(SignerSource::Prompt { derivation_path }),
+            "agent" => Ok(SignerSource::Agent {
+                socket: value.to_string(),
+                pubkey: param("pubkey")
+                    .map(|pubkey| Pubkey::from_str(pubkey).map_err(|_| invalid("invalid pubkey")))
+                    .transpose()?,
+            }),
+            // Anything else, such as a Windows drive letter, is part of a file path
+            _ => Ok(SignerSource::File(s.to_string())),
+        }
+    }
+}
+
+impl SignerSource {
+    /// Loads the signer, prompting for a secret if the source requires one.
+    pub fn load(&self) -> Result<SharedSigner, OreCliError> {
+        match self {
+            #[cfg(unix)]
+            SignerSource::Agent { socket, pubkey } => {
+                Ok(Arc::new(AgentSigner::connect(socket, *pubkey)?))
+            }
+            #[cfg(not(unix))]
+            SignerSource::Agent { .. } => Err(OreCliError::Keypair(
+                "signing agents are only supported on Unix".into(),
+            )),
+            _ => Ok(Arc::new(self.load_keypair()?)),
+        }
+    }
+
+    /// Loads the keypair of a source that holds its secret key locally.
+    pub fn load_keypair(&self) -> Result<Keypair, OreCliError> {
+        match self {
+            SignerSource::File(path) => read_keypair(path),
+            SignerSource::Env(var) => {
+                let secret = std::env::var(var).map_err(|_| {
+                    OreCliError::Keypair(format!("environment variable {} is not set", var))
+                })?;
+                parse_secret_key(&secret)
+            }
+            SignerSource::SeedPhrase {
+                var,
+                derivation_path,
+            } => {
+                let phrase = std::env::var(var).map_err(|_| {
+                    OreCliError::Keypair(format!("environment variable {} is not set", var))
+                })?;
+                keypair_from_seed_phrase(&phrase, derivation_path.clone())
+            }
+            SignerSource::Prompt { derivation_path } => {
+                let secret =
+                    rpassword::prompt_password("Seed phrase or secret key: ").map_err(|err| {
+                        OreCliError::Keypair(format!("failed to read secret: {}", err))
+                    })?;
+                let secret = secret.trim();
+                if secret.contains(char::is_whitespace) {
+                    keypair_from_seed_phrase(secret, derivation_path.clone())
+                } else {
+                    parse_secret_key(secret)
+                }
+            }
+            SignerSource::Agent { .. } => Err(OreCliError::Keypair(
+                "the secret key of an agent's key cannot be read".into(),
+            )),
+            SignerSource::Keystore(name) => keys::read_key(&keys::key_path(name)?),
+        }
+    }
+}
+
+/// A signer source that is loaded the first time it is used and reused afterwards.
+pub struct LazySigner {
+    source: SignerSource,
+    signer: Mutex<Option<SharedSigner>>,
+}
+
+impl LazySigner {
+    pub fn new(source: SignerSource) -> Self {
+        Self {
+            source,
+            signer: Mutex::new(None),
+        }
+    }
+
+    pub fn get(&self) -> Result<SharedSigner, OreCliError> {
+        let mut signer = self.signer.lock().expect("failed to lock mutex");
+        if let Some(signer) = &*signer {
+            return Ok(signer.clone());
+        }
+        let loaded = self.source.load()?;
+        *signer = Some(loaded.clone());
+        Ok(loaded)
+    }
+}
+
+/// Signers of the miner's transactions.
+pub struct SignerConfig {
+    /// Signer of the Ore authority.
+    pub authority: Option<LazySigner>,
+
+    /// Signer that pays transaction fees, tips and rent. Defaults to the authority.
+    pub fee_payer: Option<LazySigner>,
+}
+
+impl Miner {
+    pub fn signer(&self) -> Result<SharedSigner, OreCliError> {
+        match &self.signers.authority {
+            Some(signer) => signer.get(),
+            None => Err(OreCliError::Keypair("No keypair provided".into())),
+        }
+    }
+
+    /// Returns the configured fee payer, or `None` if the authority pays for its own
+    /// transactions.
+    pub fn fee_payer(&self) -> Result<Option<SharedSigner>, OreCliError> {
+        self.signers
+            .fee_payer
+            .as_ref()
+            .map(LazySigner::get)
+            .transpose()
+    }
+}
+
+/// Returns the signers of a transaction paid for by `payer` on behalf of `signer`.
+pub fn transaction_signers<'a>(
+    payer: &'a dyn Signer,
+    signer: &'a dyn Signer,
+) -> Vec<&'a dyn Signer> {
+    if payer.pubkey() == signer.pubkey() {
+        vec![signer]
+    } else {
+        vec![payer, signer]
+    }
+}
+
+/// Parses a secret key encoded as base58 or as a JSON array of bytes.
+fn parse_secret_key(secret: &str) -> Result<Keypair, OreCliError> {
+    let bytes = if secret.trim_start().starts_with('[') {
+        serde_json::from_str::<Vec<u8>>(secret).ok()
+    } else {
+        bs58::decode(secret.trim()).into_vec().ok()
+    };
+    bytes
+        .and_then(|bytes| Keypair::from_bytes(&bytes).ok())
+        .ok_or_else(|| OreCliError::Keypair("invalid secret key".into()))
+}
+
+/// Derives a keypair from a BIP39 seed phrase the way the Solana CLI does: from the seed itself
+/// if no derivation path is given, and along the BIP44 path otherwise.
+fn keypair_from_seed_phrase(
+    phrase: &str,
+    derivation_path: Option<DerivationPath>,
+) -> Result<Keypair, OreCliError> {
+    let mnemonic = Mnemonic::from_phrase(phrase.trim(), Language::English)
+        .map_err(|err| OreCliError::Keypair(format!("invalid seed phrase: {}", err)))?;
+    let seed = Seed::new(&mnemonic, "");
+    let keypair = match derivation_path {
+        Some(derivation_path) => {
+            keypair_from_seed_and_derivation_path(seed.as_bytes(), Some(derivation_path))
+        }
+        None => keypair_from_seed(seed.as_bytes()),
+    };
+    keypair.map_err(|err| OreCliError::Keypair(format!("failed to derive keypair: {}", err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use solana_sdk::derivation_path::DerivationPath;
+
+    use super::SignerSource;
+
+    #[test]
+    fn parses_signer_sources() {
+        assert_eq!(
+            SignerSource::from_str("/keys/id.json").unwrap(),
+            SignerSource::File("/keys/id.json".into())
+        );
+        assert_eq!(
+            SignerSource::from_str("env:ORE_KEY").unwrap(),
+            SignerSource::Env("ORE_KEY".into())
+        );
+        assert_eq!(
+            SignerSource::from_str("seed:ORE_SEED?key=1/0").unwrap(),
+            SignerSource::SeedPhrase {
+                var: "ORE_SEED".into(),
+                derivation_path: Some(DerivationPath::new_bip44(Some(1), Some(0))),
+            }
+        );
+        assert_eq!(
+            SignerSource::from_str("agent:/run/ore.sock").unwrap(),
+            SignerSource::Agent {
+                socket: "/run/ore.sock".into(),
+                pubkey: None,
+            }
+        );
+        assert!(SignerSource::from_str("agent:/run/ore.sock?pubkey=bad").is_err());
+        assert_eq!(
+            SignerSource::from_str("keystore:miner").unwrap(),
+            SignerSource::Keystore("miner".into())
+        );
+    }
+}
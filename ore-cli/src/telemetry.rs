@@ -0,0 +1,172 @@
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use solana_sdk::keccak::Hash as KeccakHash;
+
+/// Shared counters updated by the hashing threads of a single challenge search.
+pub struct HashTelemetry {
+    started_at: Instant,
+    expected_hashes: f64,
+    nonces: Vec<AtomicU64>,
+}
+
+/// A point-in-time view of a challenge search.
+#[derive(Clone, Debug)]
+pub struct HashrateSnapshot {
+    /// Time since the search started.
+    pub elapsed: Duration,
+
+    /// Nonces checked by each thread.
+    pub thread_nonces: Vec<u64>,
+
+    /// Nonces checked across all threads.
+    pub total_nonces: u64,
+
+    /// Hashes per second of each thread.
+    pub thread_hashrates: Vec<f64>,
+
+    /// Hashes per second across all threads.
+    pub hashrate: f64,
+
+    /// Expected time to find a valid hash at the current hashrate.
+    pub expected_time: Option<Duration>,
+}
+
+impl HashTelemetry {
+    pub fn new(threads: u64, difficulty: KeccakHash) -> Self {
+        Self {
+            started_at: Instant::now(),
+            expected_hashes: expected_hashes(difficulty),
+            nonces: (0..threads).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    /// Adds `count` checked nonces to the counter of thread `i`.
+    pub fn record(&self, i: usize, count: u64) {
+        self.nonces[i].fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HashrateSnapshot {
+        let elapsed = self.started_at.elapsed();
+        let secs = elapsed.as_secs_f64();
+        let thread_nonces: Vec<u64> = self
+            .nonces
+            .iter()
+            .map(|n| n.load(Ordering::Relaxed))
+            .collect();
+        let total_nonces = thread_nonces.iter().sum();
+        let rate = |n: u64| if secs > 0. { n as f64 / secs } else { 0. };
+        let thread_hashrates = thread_nonces.iter().map(|n| rate(*n)).collect();
+        let hashrate = rate(total_nonces);
+        let expected_time = if hashrate > 0. {
+            Duration::try_from_secs_f64(self.expected_hashes / hashrate).ok()
+        } else {
+            None
+        };
+        HashrateSnapshot {
+            elapsed,
+            thread_nonces,
+            total_nonces,
+            thread_hashrates,
+            hashrate,
+            expected_time,
+        }
+    }
+}
+
+impl fmt::Display for HashrateSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} hashes in {:.1}s ({}/s",
+            self.total_nonces,
+            self.elapsed.as_secs_f64(),
+            format_hashrate(self.hashrate),
+        )?;
+        if self.thread_hashrates.len() > 1 {
+            let threads: Vec<String> = self
+                .thread_hashrates
+                .iter()
+                .map(|h| format_hashrate(*h))
+                .collect();
+            write!(f, " [{}]", threads.join(" "))?;
+        }
+        write!(f, ")")?;
+        if let Some(expected_time) = self.expected_time {
+            write!(f, ", expected {:.1}s", expected_time.as_secs_f64())?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the expected number of hashes needed to find one that is less than or equal to
+/// `difficulty`, treating both as 256-bit big-endian integers.
+pub fn expected_hashes(difficulty: KeccakHash) -> f64 {
+    let threshold = difficulty
+        .to_bytes()
+        .iter()
+        .fold(0f64, |acc, b| acc * 256. + *b as f64);
+    2f64.powi(256) / (threshold + 1.)
+}
+
+/// Formats hashes per second with a metric prefix, such as `1.50 MH`.
+pub fn format_hashrate(hashrate: f64) -> String {
+    if hashrate >= 1e6 {
+        format!("{:.2} MH", hashrate / 1e6)
+    } else if hashrate >= 1e3 {
+        format!("{:.2} KH", hashrate / 1e3)
+    } else {
+        format!("{:.0} H", hashrate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use solana_sdk::keccak::Hash as KeccakHash;
+
+    use super::{expected_hashes, format_hashrate, HashTelemetry};
+
+    #[test]
+    fn expected_hashes_match_the_difficulty() {
+        let mut difficulty = [0xff; 32];
+        assert_eq!(expected_hashes(KeccakHash::new_from_array(difficulty)), 1.);
+        difficulty[0] = 0x0f;
+        assert_eq!(expected_hashes(KeccakHash::new_from_array(difficulty)), 16.);
+        difficulty[0] = 0;
+        difficulty[1] = 0x0f;
+        assert_eq!(expected_hashes(KeccakHash::new_from_array(difficulty)), 4096.);
+    }
+
+    #[test]
+    fn formats_hashrates_with_metric_prefixes() {
+        assert_eq!(format_hashrate(0.), "0 H");
+        assert_eq!(format_hashrate(999.4), "999 H");
+        assert_eq!(format_hashrate(2_500.), "2.50 KH");
+        assert_eq!(format_hashrate(1_500_000.), "1.50 MH");
+    }
+
+    #[test]
+    fn snapshots_sum_thread_counters() {
+        let mut difficulty = [0xff; 32];
+        difficulty[0] = 0x0f;
+        let telemetry = HashTelemetry::new(2, KeccakHash::new_from_array(difficulty));
+        telemetry.record(0, 30);
+        telemetry.record(1, 10);
+        telemetry.record(0, 20);
+        std::thread::sleep(Duration::from_millis(10));
+        let snapshot = telemetry.snapshot();
+        assert_eq!(snapshot.thread_nonces, vec![50, 10]);
+        assert_eq!(snapshot.total_nonces, 60);
+        assert!(snapshot.hashrate > 0.);
+        let thread_total: f64 = snapshot.thread_hashrates.iter().sum();
+        assert!((thread_total - snapshot.hashrate).abs() < 1e-6 * snapshot.hashrate);
+        let expected_time = snapshot.expected_time.unwrap().as_secs_f64();
+        let elapsed = snapshot.elapsed.as_secs_f64();
+        assert!((expected_time - 16. / 60. * elapsed).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,135 @@
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use rand::seq::SliceRandom;
+use solana_program::instruction::Instruction;
+use solana_sdk::{pubkey, pubkey::Pubkey, system_instruction};
+
+/// The default accounts to send tips to.
+pub const TIP_ACCOUNTS: &[Pubkey] = &[
+    pubkey!("96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5"),
+    pubkey!("HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe"),
+    pubkey!("Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY"),
+    pubkey!("ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49"),
+    pubkey!("DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh"),
+    pubkey!("ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt"),
+    pubkey!("DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL"),
+    pubkey!("3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT"),
+];
+
+const SPEND_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Tip settings shared by every transaction the miner sends.
+pub struct TipConfig {
+    /// Lamports to tip per transaction. Zero disables tipping.
+    pub lamports: u64,
+
+    /// Accounts to pick a tip recipient from.
+    pub accounts: Vec<Pubkey>,
+
+    /// Maximum lamports to spend on tips in any one hour window.
+    pub max_per_hour: Option<u64>,
+
+    spent: Mutex<VecDeque<(Instant, u64)>>,
+}
+
+impl TipConfig {
+    pub fn new(lamports: u64, accounts: Vec<Pubkey>, max_per_hour: Option<u64>) -> Self {
+        let accounts = if accounts.is_empty() {
+            TIP_ACCOUNTS.to_vec()
+        } else {
+            accounts
+        };
+        Self {
+            lamports,
+            accounts,
+            max_per_hour,
+            spent: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Returns a tip transfer from `payer` to a random tip account, or `None` if tipping is
+    /// disabled or the tip would exceed the hourly spend limit. The tip only counts against the
+    /// limit once it is passed to `record_tip`.
+    pub fn tip_ix(&self, payer: &Pubkey) -> Option<Instruction> {
+        if self.lamports == 0 {
+            return None;
+        }
+        if let Some(max_per_hour) = self.max_per_hour {
+            let total = self.spent_in_window();
+            if total.saturating_add(self.lamports) > max_per_hour {
+                println!(
+                    "Skipping tip: {} lamports spent in the last hour (max {})",
+                    total, max_per_hour
+                );
+                return None;
+            }
+        }
+        let tip_account = self.accounts.choose(&mut rand::thread_rng())?;
+        Some(system_instruction::transfer(
+            payer,
+            tip_account,
+            self.lamports,
+        ))
+    }
+
+    /// Counts a tip that landed against the hourly spend limit.
+    pub fn record_tip(&self) {
+        let mut spent = self.spent.lock().expect("failed to lock mutex");
+        spent.push_back((Instant::now(), self.lamports));
+    }
+
+    /// Returns the lamports tipped in the last hour, forgetting older tips.
+    fn spent_in_window(&self) -> u64 {
+        let mut spent = self.spent.lock().expect("failed to lock mutex");
+        while spent
+            .front()
+            .map_or(false, |(at, _)| at.elapsed() > SPEND_WINDOW)
+        {
+            spent.pop_front();
+        }
+        spent.iter().map(|(_, lamports)| lamports).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::{TipConfig, TIP_ACCOUNTS};
+
+    #[test]
+    fn stops_tipping_at_the_hourly_limit() {
+        let payer = Pubkey::new_unique();
+        let tip = TipConfig::new(400, vec![], Some(1_000));
+        let ix = tip.tip_ix(&payer).unwrap();
+        assert!(TIP_ACCOUNTS.contains(&ix.accounts[1].pubkey));
+        tip.record_tip();
+        assert!(tip.tip_ix(&payer).is_some());
+        tip.record_tip();
+        // A third tip would bring the hour's spend to 1,200 lamports
+        assert!(tip.tip_ix(&payer).is_none());
+        assert!(tip.tip_ix(&payer).is_none());
+    }
+
+    #[test]
+    fn only_landed_tips_count_against_the_limit() {
+        let payer = Pubkey::new_unique();
+        let tip = TipConfig::new(400, vec![], Some(1_000));
+        for _ in 0..5 {
+            assert!(tip.tip_ix(&payer).is_some());
+        }
+        tip.record_tip();
+        tip.record_tip();
+        assert!(tip.tip_ix(&payer).is_none());
+    }
+
+    #[test]
+    fn zero_lamports_disables_tipping() {
+        let tip = TipConfig::new(0, vec![], None);
+        assert!(tip.tip_ix(&Pubkey::new_unique()).is_none());
+    }
+}
@@ -1,31 +1,48 @@
-use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_sdk::commitment_config::CommitmentConfig;
+use ore::TREASURY_ADDRESS;
+use serde_json::json;
+use spl_token::amount_to_ui_amount;
 
 use crate::{
-    utils::{get_treasury, treasury_tokens_pubkey},
+    error::OreCliError,
+    output::ore_amount,
+    utils::{get_mining_state, treasury_tokens_pubkey},
     Miner,
 };
 
 impl Miner {
-    pub async fn treasury(&self) {
-        let client =
-            RpcClient::new_with_commitment(self.cluster.clone(), CommitmentConfig::confirmed());
-        if let Ok(Some(treasury_tokens)) = client.get_token_account(&treasury_tokens_pubkey()).await
-        {
-            let treasury = get_treasury(self.cluster.clone()).await;
-            let balance = treasury_tokens.token_amount.ui_amount_string;
-            println!("{:} ORE", balance);
-            println!("Admin: {}", treasury.admin);
-            println!("Difficulty: {}", treasury.difficulty.to_string());
-            println!("Last reset at: {}", treasury.last_reset_at);
-            println!(
-                "Reward rate: {} ORE",
-                (treasury.reward_rate as f64) / 10f64.powf(ore::TOKEN_DECIMALS as f64)
-            );
-            println!(
-                "Total claimed rewards: {} ORE",
-                (treasury.total_claimed_rewards as f64) / 10f64.powf(ore::TOKEN_DECIMALS as f64)
-            );
+    pub async fn treasury(&self) -> Result<(), OreCliError> {
+        let state = self
+            .rpc
+            .read(|client| async move { get_mining_state(&client, None).await })
+            .await?;
+        let treasury = state.treasury;
+        let amount = state.treasury_tokens.amount;
+        let json = json!({
+            "slot": state.slot,
+            "address": TREASURY_ADDRESS.to_string(),
+            "token_account": treasury_tokens_pubkey().to_string(),
+            "balance": ore_amount(amount),
+            "admin": treasury.admin.to_string(),
+            "difficulty": treasury.difficulty.to_string(),
+            "last_reset_at": treasury.last_reset_at,
+            "reward_rate": ore_amount(treasury.reward_rate),
+            "total_claimed_rewards": ore_amount(treasury.total_claimed_rewards),
+        });
+        if self.output.print_json(&json) {
+            return Ok(());
         }
+        println!("{:} ORE", amount_to_ui_amount(amount, ore::TOKEN_DECIMALS));
+        println!("Admin: {}", treasury.admin);
+        println!("Difficulty: {}", treasury.difficulty);
+        println!("Last reset at: {}", treasury.last_reset_at);
+        println!(
+            "Reward rate: {} ORE",
+            (treasury.reward_rate as f64) / 10f64.powf(ore::TOKEN_DECIMALS as f64)
+        );
+        println!(
+            "Total claimed rewards: {} ORE",
+            (treasury.total_claimed_rewards as f64) / 10f64.powf(ore::TOKEN_DECIMALS as f64)
+        );
+        Ok(())
     }
 }
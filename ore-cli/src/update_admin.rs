@@ -1,17 +1,11 @@
-use std::str::FromStr;
-
-use solana_program::pubkey::Pubkey;
-use solana_sdk::signature::Signer;
-
-use crate::Miner;
+use crate::{error::OreCliError, utils::parse_address, Miner};
 
 impl Miner {
-    pub async fn update_admin(&self, new_admin: String) {
-        let signer = self.signer();
-        let new_admin = Pubkey::from_str(new_admin.as_str()).unwrap();
+    pub async fn update_admin(&self, new_admin: String) -> Result<(), OreCliError> {
+        let signer = self.signer()?;
+        let new_admin = parse_address(&new_admin)?;
         let ix = ore::instruction::update_admin(signer.pubkey(), new_admin);
-        self.send_and_confirm(&[ix], false)
-            .await
-            .expect("Transaction failed");
+        self.send_and_confirm(signer.as_ref(), &[ix], false, false).await?;
+        Ok(())
     }
 }
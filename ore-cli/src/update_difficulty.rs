@@ -1,11 +1,10 @@
 use solana_program::keccak::Hash as KeccakHash;
-use solana_sdk::signature::Signer;
 
-use crate::Miner;
+use crate::{error::OreCliError, Miner};
 
 impl Miner {
-    pub async fn update_difficulty(&self) {
-        let signer = self.signer();
+    pub async fn update_difficulty(&self) -> Result<(), OreCliError> {
+        let signer = self.signer()?;
         // let new_difficulty = KeccakHash::new_from_array([
         //     0, 0, 0, 64, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255,
         //     255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255,
@@ -17,8 +16,7 @@ impl Miner {
         let ix = ore::instruction::update_difficulty(signer.pubkey(), new_difficulty.into());
         // let bs58data = bs58::encode(ix.data).into_string();
         // println!("Data: {:?}", bs58data);
-        self.send_and_confirm(&[ix], false)
-            .await
-            .expect("Transaction failed");
+        self.send_and_confirm(signer.as_ref(), &[ix], false, false).await?;
+        Ok(())
     }
 }
@@ -1,41 +1,114 @@
+use std::str::FromStr;
+
 use cached::proc_macro::cached;
 use ore::{
     self,
-    state::{Proof, Treasury},
+    state::{Bus, Proof, Treasury},
     utils::AccountDeserialize,
-    MINT_ADDRESS, PROOF, TREASURY_ADDRESS,
+    BUS_ADDRESSES, MINT_ADDRESS, PROOF, TREASURY_ADDRESS,
 };
 use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_program::{pubkey::Pubkey, sysvar};
-use solana_sdk::{clock::Clock, commitment_config::CommitmentConfig};
+use solana_program::{program_pack::Pack, pubkey::Pubkey, sysvar};
+use solana_sdk::{
+    account::Account,
+    clock::Clock,
+    signature::{read_keypair_file, Keypair},
+};
 use spl_associated_token_account::get_associated_token_address;
+use spl_token::state::Account as TokenAccount;
+
+use crate::error::OreCliError;
+
+/// Every account the miner reads, fetched in a single request at one slot.
+pub struct MiningState {
+    /// Slot the accounts were read at.
+    pub slot: u64,
+
+    pub treasury: Treasury,
+
+    /// The authority's proof, or `None` if it is not registered.
+    pub proof: Option<Proof>,
+
+    pub busses: Vec<Bus>,
+
+    pub clock: Clock,
+
+    /// The treasury's Ore token account.
+    pub treasury_tokens: TokenAccount,
 
-pub async fn get_treasury(cluster: String) -> Treasury {
-    let client = RpcClient::new_with_commitment(cluster, CommitmentConfig::confirmed());
-    let data = client
-        .get_account_data(&TREASURY_ADDRESS)
-        .await
-        .expect("Failed to get treasury account");
-    *Treasury::try_from_bytes(&data).expect("Failed to parse treasury account")
+    /// The authority's Ore token account, or `None` if it does not exist.
+    pub ata: Option<TokenAccount>,
 }
 
-pub async fn get_proof(cluster: String, authority: Pubkey) -> Proof {
-    let client = RpcClient::new_with_commitment(cluster, CommitmentConfig::confirmed());
-    let proof_address = proof_pubkey(authority);
-    let data = client
-        .get_account_data(&proof_address)
-        .await
-        .expect("Failed to get miner account");
-    *Proof::try_from_bytes(&data).expect("Failed to parse miner account")
+/// Fetches the treasury, its token account, busses and clock, and the proof and token account of
+/// `authority` if one is given, with one getMultipleAccounts request.
+pub async fn get_mining_state(client: &RpcClient, authority: Option<Pubkey>) -> Result<MiningState, OreCliError> {
+    let mut addresses = vec![TREASURY_ADDRESS, sysvar::clock::ID, treasury_tokens_pubkey()];
+    addresses.extend_from_slice(&BUS_ADDRESSES);
+    if let Some(authority) = authority {
+        addresses.push(proof_pubkey(authority));
+        addresses.push(get_associated_token_address(&authority, &MINT_ADDRESS));
+    }
+    let response = client
+        .get_multiple_accounts_with_commitment(&addresses, client.commitment())
+        .await?;
+    let mut accounts = response.value.into_iter();
+    let treasury = parse_account(accounts.next().flatten(), "treasury", |data| {
+        Treasury::try_from_bytes(data).ok().copied()
+    })?;
+    let clock = parse_account(accounts.next().flatten(), "clock", |data| {
+        bincode::deserialize::<Clock>(data).ok()
+    })?;
+    let treasury_tokens = parse_account(accounts.next().flatten(), "treasury token", |data| {
+        TokenAccount::unpack(data).ok()
+    })?;
+    let busses = (0..BUS_ADDRESSES.len())
+        .map(|_| {
+            parse_account(accounts.next().flatten(), "bus", |data| {
+                Bus::try_from_bytes(data).ok().copied()
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // The authority may not have created its proof or token account yet
+    let proof = accounts
+        .next()
+        .flatten()
+        .map(|account| {
+            parse_account(Some(account), "proof", |data| {
+                Proof::try_from_bytes(data).ok().copied()
+            })
+        })
+        .transpose()?;
+    let ata = accounts
+        .next()
+        .flatten()
+        .map(|account| parse_account(Some(account), "token", |data| TokenAccount::unpack(data).ok()))
+        .transpose()?;
+    Ok(MiningState {
+        slot: response.context.slot,
+        treasury,
+        proof,
+        busses,
+        clock,
+        treasury_tokens,
+        ata,
+    })
+}
+
+/// Parses the data of `account` with `parse`, failing if the account is missing or malformed.
+fn parse_account<T>(
+    account: Option<Account>,
+    name: &str,
+    parse: impl FnOnce(&[u8]) -> Option<T>,
+) -> Result<T, OreCliError> {
+    let account = account.ok_or_else(|| OreCliError::AccountNotFound(format!("{} account", name)))?;
+    parse(&account.data).ok_or_else(|| OreCliError::Deserialization(format!("{} account", name)))
 }
 
-pub async fn get_clock_account(cluster: String) -> Clock {
-    let client = RpcClient::new_with_commitment(cluster, CommitmentConfig::confirmed());
-    let data = client
-        .get_account_data(&sysvar::clock::ID)
-        .await
-        .expect("Failed to get miner account");
-    bincode::deserialize::<Clock>(&data).expect("Failed to deserialize clock")
+/// Parses a base58 account address given on the command line.
+pub fn parse_address(address: &str) -> Result<Pubkey, OreCliError> {
+    Pubkey::from_str(address).map_err(|_| OreCliError::InvalidInput(format!("Invalid address: {}", address)))
 }
 
 #[cached]
@@ -47,3 +120,44 @@ pub fn proof_pubkey(authority: Pubkey) -> Pubkey {
 pub fn treasury_tokens_pubkey() -> Pubkey {
     get_associated_token_address(&TREASURY_ADDRESS, &MINT_ADDRESS)
 }
+
+/// Reads the keypair file at `path`.
+pub fn read_keypair(path: &str) -> Result<Keypair, OreCliError> {
+    read_keypair_file(path).map_err(|err| OreCliError::Keypair(format!("{}: {}", path, err)))
+}
+
+/// Returns the paths of the keypair files in `dir`, in order of their names.
+pub fn keypair_dir_paths(dir: &str) -> Result<Vec<String>, OreCliError> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|err| OreCliError::Keypair(format!("{}: {}", dir, err)))?;
+    let mut paths: Vec<String> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().map_or(false, |ext| ext == "json"))
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    if paths.is_empty() {
+        return Err(OreCliError::Keypair(format!("No keypair files found in {}", dir)));
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Returns the strings listed under `key` in a Solana CLI config file, or none if the file or
+/// key is missing.
+pub fn config_file_list(config_file: &str, key: &str) -> Vec<String> {
+    let Ok(contents) = std::fs::read_to_string(config_file) else {
+        return vec![];
+    };
+    let Ok(config) = serde_yaml::from_str::<serde_yaml::Value>(&contents) else {
+        return vec![];
+    };
+    config[key]
+        .as_sequence()
+        .map(|values| {
+            values
+                .iter()
+                .filter_map(|value| value.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,225 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    str::FromStr,
+};
+
+use serde_json::{json, Value};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{keccak::Hash as KeccakHash, signature::Signature};
+
+use crate::{error::OreCliError, mine::solution_hash, utils::parse_address, Miner};
+
+/// A nonce submitted as a solution to a proof's challenge.
+pub struct Solution {
+    pub challenge: KeccakHash,
+    pub authority: Pubkey,
+    pub nonce: u64,
+    pub difficulty: KeccakHash,
+}
+
+impl Solution {
+    /// Returns the hash of the nonce, computed exactly as the miner does.
+    pub fn hash(&self) -> KeccakHash {
+        solution_hash(self.challenge, self.authority, self.nonce)
+    }
+
+    /// Returns whether the hash of the nonce satisfies the difficulty.
+    pub fn is_valid(&self) -> bool {
+        self.hash().le(&self.difficulty)
+    }
+
+    /// Parses a solution from the `challenge`, `authority`, `nonce` and `difficulty` fields of a
+    /// submission log entry.
+    fn from_json(value: &Value) -> Result<Self, OreCliError> {
+        let field = |name: &str| {
+            value[name]
+                .as_str()
+                .ok_or_else(|| OreCliError::InvalidInput(format!("missing field `{}`", name)))
+        };
+        Ok(Self {
+            challenge: parse_hash(field("challenge")?)?,
+            authority: parse_address(field("authority")?)?,
+            nonce: value["nonce"]
+                .as_u64()
+                .ok_or_else(|| OreCliError::InvalidInput("missing field `nonce`".into()))?,
+            difficulty: parse_hash(field("difficulty")?)?,
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "challenge": self.challenge.to_string(),
+            "authority": self.authority.to_string(),
+            "nonce": self.nonce,
+            "difficulty": self.difficulty.to_string(),
+            "hash": self.hash().to_string(),
+            "valid": self.is_valid(),
+        })
+    }
+}
+
+impl Miner {
+    /// Verifies a single solution, or every solution in the JSONL file at `file`.
+    pub fn verify(&self, solution: Option<Solution>, file: Option<String>) -> Result<(), OreCliError> {
+        if let Some(solution) = solution {
+            if self.output.print_json(&solution.to_json()) {
+                return Ok(());
+            }
+            println!("Hash: {}", solution.hash());
+            println!("Difficulty: {}", solution.difficulty);
+            println!("Valid: {}", if solution.is_valid() { "yes" } else { "no" });
+            return Ok(());
+        }
+
+        let Some(path) = file else {
+            return Err(OreCliError::InvalidInput("No solution or file to verify".into()));
+        };
+        let file = File::open(&path)
+            .map_err(|err| OreCliError::InvalidInput(format!("Failed to open {}: {}", path, err)))?;
+        let mut results = vec![];
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line
+                .map_err(|err| OreCliError::InvalidInput(format!("Failed to read {}: {}", path, err)))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let solution = serde_json::from_str(&line)
+                .map_err(|err| OreCliError::InvalidInput(err.to_string()))
+                .and_then(|value| Solution::from_json(&value))
+                .map_err(|err| OreCliError::InvalidInput(format!("{}:{}: {}", path, i + 1, err)))?;
+            results.push((i + 1, solution));
+        }
+
+        let valid = results.iter().filter(|(_, solution)| solution.is_valid()).count();
+        let json = json!({
+            "solutions": results
+                .iter()
+                .map(|(line, solution)| {
+                    let mut json = solution.to_json();
+                    json["line"] = json!(line);
+                    json
+                })
+                .collect::<Vec<_>>(),
+            "valid": valid,
+            "total": results.len(),
+        });
+        if self.output.print_json(&json) {
+            return Ok(());
+        }
+        for (line, solution) in &results {
+            let status = if solution.is_valid() { "valid" } else { "invalid" };
+            println!("Line {}: {} (nonce {}, hash {})", line, status, solution.nonce, solution.hash());
+        }
+        println!("{} of {} solutions valid", valid, results.len());
+        Ok(())
+    }
+}
+
+/// Returns the solution given on the command line, or `None` if any part of it is missing.
+pub fn parse_solution(
+    challenge: Option<String>,
+    authority: Option<String>,
+    nonce: Option<u64>,
+    difficulty: Option<String>,
+) -> Result<Option<Solution>, OreCliError> {
+    let (Some(challenge), Some(authority), Some(nonce), Some(difficulty)) =
+        (challenge, authority, nonce, difficulty)
+    else {
+        return Ok(None);
+    };
+    Ok(Some(Solution {
+        challenge: parse_hash(&challenge)?,
+        authority: parse_address(&authority)?,
+        nonce,
+        difficulty: parse_hash(&difficulty)?,
+    }))
+}
+
+/// Parses a base58 encoded hash.
+fn parse_hash(hash: &str) -> Result<KeccakHash, OreCliError> {
+    KeccakHash::from_str(hash).map_err(|_| OreCliError::InvalidInput(format!("Invalid hash: {}", hash)))
+}
+
+/// Appends a submitted solution, the bus it was sent to and its result to the JSONL file at
+/// `path`. Failures are logged rather than interrupting mining.
+pub fn log_submission(path: &str, solution: &Solution, bus: u64, result: &Result<Signature, OreCliError>) {
+    let mut entry = solution.to_json();
+    entry["submitted_at"] = json!(chrono::Utc::now().to_rfc3339());
+    entry["bus"] = json!(bus);
+    match result {
+        Ok(signature) => entry["signature"] = json!(signature.to_string()),
+        Err(err) => entry["error"] = json!(err.to_string()),
+    }
+    let written = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{}", entry));
+    if let Err(err) = written {
+        println!("Failed to write submission log {}: {}", path, err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::Value;
+    use solana_program::pubkey::Pubkey;
+    use solana_sdk::{keccak::Hash as KeccakHash, signature::Signature};
+
+    use super::{log_submission, parse_hash, Solution};
+    use crate::error::OreCliError;
+
+    fn test_solution(difficulty: KeccakHash) -> Solution {
+        Solution {
+            challenge: KeccakHash::new_from_array([1; 32]),
+            authority: Pubkey::new_from_array([2; 32]),
+            nonce: 42,
+            difficulty,
+        }
+    }
+
+    #[test]
+    fn checks_the_hash_against_the_difficulty() {
+        let expected = parse_hash("DtkUHtuHTPDDc6FBF2zutRpCtMkTAcfyUQDqdHpusf7T").unwrap();
+        let solution = test_solution(expected);
+        assert_eq!(solution.hash(), expected);
+        assert!(solution.is_valid());
+        assert!(test_solution(KeccakHash::new_from_array([0xff; 32])).is_valid());
+        assert!(!test_solution(KeccakHash::new_from_array([0; 32])).is_valid());
+    }
+
+    #[test]
+    fn logged_submissions_round_trip() {
+        let path = std::env::temp_dir().join(format!("ore-cli-submissions-{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+        let solution = test_solution(KeccakHash::new_from_array([0xff; 32]));
+        let signature = Signature::from([3; 64]);
+        log_submission(path, &solution, 4, &Ok(signature));
+        log_submission(path, &solution, 5, &Err(OreCliError::InvalidInput("rejected".into())));
+        let contents = fs::read_to_string(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        let entries = contents
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+        for entry in &entries {
+            let logged = Solution::from_json(entry).unwrap();
+            assert_eq!(logged.challenge, solution.challenge);
+            assert_eq!(logged.authority, solution.authority);
+            assert_eq!(logged.nonce, solution.nonce);
+            assert_eq!(logged.difficulty, solution.difficulty);
+            assert_eq!(entry["hash"], solution.hash().to_string());
+            assert_eq!(entry["valid"], true);
+        }
+        assert_eq!(entries[0]["bus"], 4);
+        assert_eq!(entries[0]["signature"], signature.to_string());
+        assert_eq!(entries[1]["bus"], 5);
+        assert!(entries[1]["error"].as_str().unwrap().contains("rejected"));
+    }
+}